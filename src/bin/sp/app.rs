@@ -19,6 +19,25 @@ pub(crate) fn app() -> App<'static, 'static> {
                 .help("Runs the command in a subshell and displays its output and error streams")
                 .multiple(true),
         )
+        .arg(
+            Arg::with_name("pty")
+                .long("pty")
+                .short("t")
+                .help("Runs commands with their output and error streams attached to pseudo-terminals"),
+        )
+        .arg(
+            Arg::with_name("input")
+                .long("input")
+                .short("i")
+                .help("Keeps the standard input of commands open for input typed into the pager"),
+        )
+        .arg(
+            Arg::with_name("watch")
+                .long("watch")
+                .short("w")
+                .value_name("SECONDS")
+                .help("Runs commands again every SECONDS seconds, marking lines that change"),
+        )
         .arg(
             Arg::with_name("fullscreen")
                 .long("fullscreen")
@@ -16,7 +16,7 @@ use std::time::Duration;
 use termwiz::istty::IsTty;
 use vec_map::VecMap;
 
-use streampager::{config::InterfaceMode, config::WrappingMode, Pager};
+use streampager::{config::parse_interval, config::InterfaceMode, config::WrappingMode, Pager};
 
 mod app;
 
@@ -65,6 +65,18 @@ fn open_files(args: ArgMatches) -> Result<(), Error> {
         }
     }
 
+    if args.is_present("pty") {
+        pager.set_pty(true);
+    }
+
+    if args.is_present("input") {
+        pager.set_forward_input(true);
+    }
+
+    if let Some(seconds) = args.value_of("watch") {
+        pager.set_watch_interval(Some(parse_interval(seconds)?));
+    }
+
     if args.is_present("no_alternate") {
         pager.set_wrapping_mode(WrappingMode::GraphemeBoundary);
     }
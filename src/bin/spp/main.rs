@@ -8,7 +8,7 @@ use std::env;
 use std::ffi::{OsStr, OsString};
 use std::fmt::Write;
 
-use streampager::Pager;
+use streampager::{config::parse_interval, Pager};
 
 /// Main.
 fn main() {
@@ -30,16 +30,38 @@ fn main() {
 /// Start a command and page the output.
 fn start_command() -> Result<(), Error> {
     let mut pager = Pager::new_using_system_terminal()?;
-    let args: Vec<_> = env::args_os().collect();
-    if args.len() < 2 {
+    let mut args: Vec<_> = env::args_os().skip(1).collect();
+    // Options may be given before the command.
+    loop {
+        match args.first().and_then(|arg| arg.to_str()) {
+            Some("--pty") | Some("-t") => {
+                pager.set_pty(true);
+                args.remove(0);
+            }
+            Some("--input") | Some("-i") => {
+                pager.set_forward_input(true);
+                args.remove(0);
+            }
+            Some("--watch") | Some("-w") => {
+                let seconds = match args.get(1) {
+                    Some(seconds) => seconds.to_string_lossy(),
+                    None => bail!("expected interval for --watch"),
+                };
+                pager.set_watch_interval(Some(parse_interval(&seconds)?));
+                args.drain(..2);
+            }
+            _ => break,
+        }
+    }
+    if args.is_empty() {
         bail!("expected command to run")
     }
-    let title = &args[1..]
+    let title = &args
         .iter()
         .map(OsString::as_os_str)
         .map(OsStr::to_string_lossy)
         .collect::<Vec<_>>()
         .join(" ");
-    pager.add_subprocess(&args[1], &args[2..], &title)?;
+    pager.add_subprocess(&args[0], &args[1..], &title)?;
     pager.run()
 }
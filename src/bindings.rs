@@ -67,12 +67,53 @@ pub enum Binding {
     /// Cancel the current action.
     Cancel,
 
+    /// Prompt the user for a path to save the current file to.
+    PromptSaveFile,
+
+    /// Prompt the user for a range of lines and a shell command to pipe them
+    /// to.
+    PromptPipe,
+
+    /// Open the file in the user's editor.
+    OpenEditor,
+
+    /// Suspend the pager, returning to the shell that started it.
+    Suspend,
+
+    /// Prompt the user for a shell command to run.
+    PromptShellCommand,
+
+    /// Prompt the user for a file to open.
+    PromptOpenFile,
+
+    /// Close the current file.
+    CloseFile,
+
+    /// Run the command whose output is being displayed again.
+    RerunCommand,
+
+    /// Interrupt the command whose output is being displayed.
+    InterruptCommand,
+
+    /// Ask the command whose output is being displayed to terminate.
+    TerminateCommand,
+
+    /// Kill the command whose output is being displayed.
+    KillCommand,
+
+    /// Prompt the user for lines of input to forward to the command whose
+    /// output is being displayed.
+    PromptCommandInput,
+
     /// Switch to the previous file.
     PreviousFile,
 
     /// Switch to the next file.
     NextFile,
 
+    /// Show the list of open files, and prompt the user for one to switch to.
+    ShowFileList,
+
     /// Scroll up *n* lines.
     ScrollUpLines(usize),
 
@@ -109,9 +150,39 @@ pub enum Binding {
     /// Toggle line wrapping mode.
     ToggleLineWrapping,
 
+    /// Prompt the user for a pattern to highlight.
+    PromptHighlight,
+
+    /// Remove the most recently added highlight.
+    RemoveHighlight,
+
+    /// Remove all highlights.
+    ClearHighlights,
+
     /// Prompt the user for a line to move to.
     PromptGoToLine,
 
+    /// Return to the position before the most recent jump.
+    JumpBack,
+
+    /// Return to the position that was left by the most recent `JumpBack`.
+    JumpForward,
+
+    /// Prompt the user for a mark to set at the top line of the screen.
+    PromptSetMark,
+
+    /// Prompt the user for a mark to move to.
+    PromptGoToMark,
+
+    /// Set a mark at the top line of the screen.
+    SetMark(char),
+
+    /// Move to a mark.
+    GoToMark(char),
+
+    /// Show the list of marks.
+    ShowMarks,
+
     /// Prompt the user for a search term.  The search will start at the beginning of the file.
     PromptSearchFromStart,
 
@@ -122,6 +193,9 @@ pub enum Binding {
     /// proceed backwards.
     PromptSearchBackwards,
 
+    /// Prompt the user for a search term, and search for it in all files.
+    PromptSearchAllFiles,
+
     /// Move to the previous match.
     PreviousMatch,
 
@@ -140,6 +214,20 @@ pub enum Binding {
     /// Move to the last match.
     LastMatch,
 
+    /// Prompt the user for a pattern to filter the file by.  Only lines that match the pattern
+    /// will be shown.
+    PromptFilter,
+
+    /// Prompt the user for a pattern to filter the file by.  Lines that match the pattern will be
+    /// hidden.
+    PromptFilterOut,
+
+    /// Remove the most recently added filter.
+    PopFilter,
+
+    /// Remove all filters.
+    ClearFilter,
+
     /// An unrecognised binding.
     Unrecognized(String),
 }
@@ -148,9 +236,12 @@ impl Binding {
     pub(crate) fn category(&self) -> Category {
         use Binding::*;
         match self {
-            Quit | Refresh | Help | Cancel => Category::General,
+            Quit | Refresh | Help | Cancel | PromptSaveFile | PromptPipe | OpenEditor | Suspend
+            | PromptShellCommand | PromptOpenFile | CloseFile | RerunCommand | InterruptCommand
+            | TerminateCommand | KillCommand | PromptCommandInput => Category::General,
             PreviousFile
             | NextFile
+            | ShowFileList
             | ScrollUpLines(_)
             | ScrollDownLines(_)
             | ScrollUpScreenFraction(_)
@@ -161,17 +252,30 @@ impl Binding {
             | ScrollRightColumns(_)
             | ScrollLeftScreenFraction(_)
             | ScrollRightScreenFraction(_)
-            | PromptGoToLine => Category::Navigation,
-            ToggleLineNumbers | ToggleLineWrapping => Category::Presentation,
+            | PromptGoToLine
+            | JumpBack
+            | JumpForward
+            | PromptSetMark
+            | PromptGoToMark
+            | SetMark(_)
+            | GoToMark(_)
+            | ShowMarks => Category::Navigation,
+            ToggleLineNumbers | ToggleLineWrapping | PromptHighlight | RemoveHighlight
+            | ClearHighlights => Category::Presentation,
             PromptSearchFromStart
             | PromptSearchForwards
             | PromptSearchBackwards
+            | PromptSearchAllFiles
             | NextMatch
             | PreviousMatch
             | NextMatchLine
             | PreviousMatchLine
             | FirstMatch
-            | LastMatch => Category::Searching,
+            | LastMatch
+            | PromptFilter
+            | PromptFilterOut
+            | PopFilter
+            | ClearFilter => Category::Searching,
             Unrecognized(_) => Category::None,
         }
     }
@@ -190,13 +294,45 @@ impl Binding {
             Ok(value)
         };
 
+        let param_char = |index| -> Result<char> {
+            let value: &String = params
+                .get(index)
+                .ok_or_else(|| anyhow!("{}: missing parameter {}", ident, index))?;
+            let quoted = value
+                .strip_prefix('\'')
+                .and_then(|value| value.strip_suffix('\''))
+                .ok_or_else(|| anyhow!("{}: parameter {} must be a character", ident, index))?;
+            let mut chars = quoted.chars();
+            match (chars.next(), chars.next(), chars.next()) {
+                (Some(c), None, None) | (Some('\\'), Some(c), None) => Ok(c),
+                _ => Err(anyhow!(
+                    "{}: parameter {} must be a character",
+                    ident,
+                    index
+                )),
+            }
+        };
+
         let binding = match ident.as_str() {
             "Quit" => Quit,
             "Refresh" => Refresh,
             "Help" => Help,
             "Cancel" => Cancel,
+            "PromptSaveFile" => PromptSaveFile,
+            "PromptPipe" => PromptPipe,
+            "OpenEditor" => OpenEditor,
+            "Suspend" => Suspend,
+            "PromptShellCommand" => PromptShellCommand,
+            "PromptOpenFile" => PromptOpenFile,
+            "CloseFile" => CloseFile,
+            "RerunCommand" => RerunCommand,
+            "InterruptCommand" => InterruptCommand,
+            "TerminateCommand" => TerminateCommand,
+            "KillCommand" => KillCommand,
+            "PromptCommandInput" => PromptCommandInput,
             "PreviousFile" => PreviousFile,
             "NextFile" => NextFile,
+            "ShowFileList" => ShowFileList,
             "ScrollUpLines" => ScrollUpLines(param_usize(0)?),
             "ScrollDownLines" => ScrollDownLines(param_usize(0)?),
             "ScrollUpScreenFraction" => ScrollUpScreenFraction(param_usize(0)?),
@@ -209,16 +345,31 @@ impl Binding {
             "ScrollRightScreenFraction" => ScrollRightScreenFraction(param_usize(0)?),
             "ToggleLineNumbers" => ToggleLineNumbers,
             "ToggleLineWrapping" => ToggleLineWrapping,
+            "PromptHighlight" => PromptHighlight,
+            "RemoveHighlight" => RemoveHighlight,
+            "ClearHighlights" => ClearHighlights,
             "PromptGoToLine" => PromptGoToLine,
+            "JumpBack" => JumpBack,
+            "JumpForward" => JumpForward,
+            "PromptSetMark" => PromptSetMark,
+            "PromptGoToMark" => PromptGoToMark,
+            "SetMark" => SetMark(param_char(0)?),
+            "GoToMark" => GoToMark(param_char(0)?),
+            "ShowMarks" => ShowMarks,
             "PromptSearchFromStart" => PromptSearchFromStart,
             "PromptSearchForwards" => PromptSearchForwards,
             "PromptSearchBackwards" => PromptSearchBackwards,
+            "PromptSearchAllFiles" => PromptSearchAllFiles,
             "PreviousMatch" => PreviousMatch,
             "NextMatch" => NextMatch,
             "PreviousMatchLine" => PreviousMatchLine,
             "NextMatchLine" => NextMatchLine,
             "FirstMatch" => FirstMatch,
             "LastMatch" => LastMatch,
+            "PromptFilter" => PromptFilter,
+            "PromptFilterOut" => PromptFilterOut,
+            "PopFilter" => PopFilter,
+            "ClearFilter" => ClearFilter,
             _ => Unrecognized(ident),
         };
 
@@ -233,9 +384,22 @@ impl std::fmt::Display for Binding {
             Quit => write!(f, "Quit"),
             Refresh => write!(f, "Refresh the screen"),
             Help => write!(f, "Show this help"),
-            Cancel => write!(f, "Close help or any open prompt"),
+            Cancel => write!(f, "Close help or any open prompt, and stop saving"),
+            PromptSaveFile => write!(f, "Save the file"),
+            PromptPipe => write!(f, "Pipe lines to a command"),
+            OpenEditor => write!(f, "Open the file in an editor"),
+            Suspend => write!(f, "Suspend the pager"),
+            PromptShellCommand => write!(f, "Run a shell command"),
+            PromptOpenFile => write!(f, "Open a file"),
+            CloseFile => write!(f, "Close the current file"),
+            RerunCommand => write!(f, "Run the command again"),
+            InterruptCommand => write!(f, "Interrupt the command"),
+            TerminateCommand => write!(f, "Terminate the command"),
+            KillCommand => write!(f, "Kill the command"),
+            PromptCommandInput => write!(f, "Send input to the command"),
             PreviousFile => write!(f, "Switch to the previous file"),
             NextFile => write!(f, "Switch to the next file"),
+            ShowFileList => write!(f, "List the open files"),
             ScrollUpLines(1) => write!(f, "Scroll up"),
             ScrollUpLines(n) => write!(f, "Scroll up {} lines", n),
             ScrollDownLines(1) => write!(f, "Scroll down"),
@@ -256,16 +420,31 @@ impl std::fmt::Display for Binding {
             ScrollRightScreenFraction(n) => write!(f, "Scroll right 1/{} screen", n),
             ToggleLineNumbers => write!(f, "Toggle line numbers"),
             ToggleLineWrapping => write!(f, "Cycle through line wrapping modes"),
+            PromptHighlight => write!(f, "Highlight a pattern"),
+            RemoveHighlight => write!(f, "Remove the most recent highlight"),
+            ClearHighlights => write!(f, "Remove all highlights"),
             PromptGoToLine => write!(f, "Go to position in file"),
+            JumpBack => write!(f, "Go back to the previous position"),
+            JumpForward => write!(f, "Go forward to the next position"),
+            PromptSetMark => write!(f, "Set a mark"),
+            PromptGoToMark => write!(f, "Go to a mark"),
+            SetMark(c) => write!(f, "Set mark '{}'", c),
+            GoToMark(c) => write!(f, "Go to mark '{}'", c),
+            ShowMarks => write!(f, "List marks"),
             PromptSearchFromStart => write!(f, "Search from the start of the file"),
             PromptSearchForwards => write!(f, "Search forwards"),
             PromptSearchBackwards => write!(f, "Search backwards"),
+            PromptSearchAllFiles => write!(f, "Search all files"),
             PreviousMatch => write!(f, "Move to the previous match"),
             NextMatch => write!(f, "Move to the next match"),
             PreviousMatchLine => write!(f, "Move to the previous matching line"),
             NextMatchLine => write!(f, "Move the the next matching line"),
             FirstMatch => write!(f, "Move to the first match"),
             LastMatch => write!(f, "Move to the last match"),
+            PromptFilter => write!(f, "Show only lines matching a pattern"),
+            PromptFilterOut => write!(f, "Hide lines matching a pattern"),
+            PopFilter => write!(f, "Remove the most recent filter"),
+            ClearFilter => write!(f, "Remove all filters"),
             Unrecognized(ref s) => write!(f, "Unrecognized binding ({})", s),
         }
     }
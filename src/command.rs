@@ -2,18 +2,28 @@
 //!
 //! Commands the user can invoke.
 use anyhow::Error;
+use std::cell::{Cell, RefCell};
+use std::cmp::{max, min};
+use std::fs;
+use std::num::ParseIntError;
+use std::path::PathBuf;
+use std::rc::Rc;
 
 use crate::display::Action;
 use crate::event::EventSender;
+use crate::file::{CommandInput, FileLines};
 use crate::prompt::Prompt;
-use crate::screen::Screen;
-use crate::search::{MatchMotion, Search, SearchKind};
+use crate::save::{self, SaveOptions};
+use crate::screen::{PipeRange, SavedPosition, Screen};
+use crate::search::{MatchMotion, Search, SearchKind, SearchOptions};
 
 /// Go to a line (Shortcut: ':')
 ///
-/// Prompts the user for a line number or percentage within the file and jumps
-/// to that position.  Negative numbers can be used to refer to locations
-/// relative to the end of the file.
+/// Prompts the user for a position within the file and jumps to it.  The
+/// position can be a line number, a percentage through the file (`50%`), a
+/// number of lines relative to the top of the screen (`+200` or `-50`), a byte
+/// offset (`b123456`), or the end of the file (`$`).  Negative percentages
+/// refer to locations relative to the end of the file.
 pub(crate) fn goto() -> Prompt {
     Prompt::new(
         "goto",
@@ -26,46 +36,12 @@ pub(crate) fn goto() -> Prompt {
                     "" => return Ok(Some(Action::Render)),
                     _ => {}
                 }
-                let lines = screen.file.lines() as isize;
-                if value.ends_with('%') {
-                    // Percentage
-                    match str::parse::<isize>(&value[..value.len() - 1]) {
-                        Ok(value_percent) => {
-                            let value_percent = if value_percent <= -100 {
-                                0
-                            } else if value_percent > 100 {
-                                100
-                            } else if value_percent < 0 {
-                                100 + value_percent
-                            } else {
-                                value_percent
-                            };
-                            let value = value_percent * (lines - 1) / 100;
-                            screen.scroll_to(value as usize);
-                        }
-                        Err(e) => {
-                            screen.error = Some(e.to_string());
-                        }
-                    }
-                } else {
-                    // Absolute
-                    match str::parse::<isize>(value) {
-                        Ok(value) => {
-                            let value = if value < -lines || value == 0 {
-                                0
-                            } else if value > lines {
-                                lines - 1
-                            } else if value < 0 {
-                                lines + value - 1
-                            } else {
-                                value - 1
-                            };
-                            screen.scroll_to(value as usize);
-                        }
-                        Err(e) => {
-                            screen.error = Some(e.to_string());
-                        }
+                match parse_goto(value, screen.file.lines(), screen.top_file_line()) {
+                    Ok(GotoPosition::Line(line)) => screen.jump_to(line),
+                    Ok(GotoPosition::Offset(offset)) => {
+                        screen.jump_to(screen.file.line_at_offset(offset))
                     }
+                    Err(e) => screen.error = Some(e.to_string()),
                 }
                 Ok(Some(Action::Render))
             },
@@ -73,30 +49,605 @@ pub(crate) fn goto() -> Prompt {
     )
 }
 
-/// Search for text (Shortcuts: '/', '<', '>')
+/// A position entered at the goto prompt.
+#[derive(Debug, PartialEq, Eq)]
+enum GotoPosition {
+    /// The index of a line.
+    Line(usize),
+
+    /// A byte offset into the file.
+    Offset(usize),
+}
+
+/// Parse a position entered at the goto prompt, for a file of `lines` lines
+/// with line `top_line` at the top of the screen.
+fn parse_goto(value: &str, lines: usize, top_line: usize) -> Result<GotoPosition, ParseIntError> {
+    let lines = lines as isize;
+    let last_line = max(lines - 1, 0);
+    if value == "$" {
+        // End of file
+        Ok(GotoPosition::Line(last_line as usize))
+    } else if let Some(value_percent) = value.strip_suffix('%') {
+        // Percentage
+        let value_percent = str::parse::<isize>(value_percent)?;
+        let value_percent = if value_percent <= -100 {
+            0
+        } else if value_percent > 100 {
+            100
+        } else if value_percent < 0 {
+            100 + value_percent
+        } else {
+            value_percent
+        };
+        Ok(GotoPosition::Line(
+            (value_percent * last_line / 100) as usize,
+        ))
+    } else if let Some(offset) = value.strip_prefix('b') {
+        // Byte offset
+        Ok(GotoPosition::Offset(str::parse::<usize>(offset)?))
+    } else if value.starts_with('+') || value.starts_with('-') {
+        // Relative to the top of the screen
+        let line = top_line as isize + str::parse::<isize>(value)?;
+        Ok(GotoPosition::Line(max(min(line, last_line), 0) as usize))
+    } else {
+        // Absolute
+        let value = str::parse::<isize>(value)?;
+        let value = if value == 0 {
+            0
+        } else if value > lines {
+            last_line
+        } else {
+            value - 1
+        };
+        Ok(GotoPosition::Line(value as usize))
+    }
+}
+
+/// Set a mark (Shortcut: 'm')
 ///
-/// Prompts the user for text to search.
-pub(crate) fn search(kind: SearchKind, event_sender: EventSender) -> Prompt {
+/// Prompts the user for a character, and marks the line at the top of the
+/// screen with it.
+pub(crate) fn set_mark() -> Prompt {
     Prompt::new(
+        "mark",
+        "Set mark:",
+        Box::new(
+            |screen: &mut Screen, value: &str| -> Result<Option<Action>, Error> {
+                if let Some(mark) = value.chars().next() {
+                    screen.set_mark(mark);
+                }
+                Ok(Some(Action::Render))
+            },
+        ),
+    )
+    .with_single_key()
+}
+
+/// Go to a mark (Shortcut: ''')
+///
+/// Prompts the user for a character, and moves to the line marked with it.
+/// The mark `'` returns to the position before the most recent jump.
+pub(crate) fn goto_mark() -> Prompt {
+    Prompt::new(
+        "mark",
+        "Go to mark:",
+        Box::new(
+            |screen: &mut Screen, value: &str| -> Result<Option<Action>, Error> {
+                if let Some(mark) = value.chars().next() {
+                    screen.goto_mark(mark);
+                }
+                Ok(Some(Action::Render))
+            },
+        ),
+    )
+    .with_single_key()
+}
+
+/// The position and search from before an incremental search started.
+type SavedSearch = (SavedPosition, Option<Search>);
+
+/// Search for text (Shortcuts: '/', '<', '>')
+///
+/// Prompts the user for text to search.  The search options can be changed
+/// while the prompt is open.
+///
+/// If `incremental` is true, the search is restarted each time the text
+/// changes.  Cancelling the prompt returns to the original position and
+/// search.
+pub(crate) fn search(
+    kind: SearchKind,
+    options: Rc<Cell<SearchOptions>>,
+    incremental: bool,
+    event_sender: EventSender,
+) -> Prompt {
+    let saved: Rc<RefCell<Option<SavedSearch>>> = Rc::new(RefCell::new(None));
+    let prompt = Prompt::new(
         "search",
         "Search:",
-        Box::new(
+        Box::new({
+            let options = options.clone();
+            let saved = saved.clone();
+            let event_sender = event_sender.clone();
             move |screen: &mut Screen, value: &str| -> Result<Option<Action>, Error> {
+                let saved = saved.borrow_mut().take();
                 screen.refresh_matched_lines();
                 if value.is_empty() {
+                    if let Some((position, search)) = saved {
+                        screen.set_search(search);
+                        screen.restore_position(position);
+                    }
                     match kind {
-                        SearchKind::First | SearchKind::FirstAfter(_) => {
+                        SearchKind::First | SearchKind::FirstAfter(_) | SearchKind::Filter => {
                             screen.move_match(MatchMotion::NextLine)
                         }
                         SearchKind::FirstBefore(_) => screen.move_match(MatchMotion::PreviousLine),
-                    }
+                    };
+                } else if let Some((position, _)) = saved {
+                    // The incremental search has already moved to the match.
+                    screen.finish_incremental_search();
+                    screen.record_jump_from(position);
                 } else {
                     screen.set_search(
-                        Search::new(&screen.file, value, kind, event_sender.clone()).ok(),
+                        Search::new(
+                            &screen.file,
+                            value,
+                            options.get(),
+                            kind,
+                            event_sender.clone(),
+                        )
+                        .ok(),
+                    );
+                }
+                Ok(Some(Action::Render))
+            }
+        }),
+    )
+    .with_options(options.clone());
+    if !incremental {
+        return prompt;
+    }
+    prompt
+        .with_change(Rc::new({
+            let saved = saved.clone();
+            move |screen: &mut Screen, value: &str| -> Result<Option<Action>, Error> {
+                let mut saved = saved.borrow_mut();
+                let (position, _) = saved.get_or_insert_with(|| {
+                    let position = screen.save_position();
+                    (position, screen.take_search())
+                });
+                let position = *position;
+                screen.refresh_matched_lines();
+                if value.is_empty() {
+                    screen.set_search(None);
+                    screen.restore_position(position);
+                } else {
+                    screen.set_incremental_search(
+                        Search::new(
+                            &screen.file,
+                            value,
+                            options.get(),
+                            kind,
+                            event_sender.clone(),
+                        )
+                        .ok(),
                     );
                 }
                 Ok(Some(Action::Render))
+            }
+        }))
+        .with_cancel(Box::new(
+            move |screen: &mut Screen| -> Result<Option<Action>, Error> {
+                if let Some((position, search)) = saved.borrow_mut().take() {
+                    screen.set_search(search);
+                    screen.restore_position(position);
+                }
+                Ok(Some(Action::Render))
+            },
+        ))
+}
+
+/// Search all files (Shortcut: Alt-'/')
+///
+/// Prompts the user for text to search for in every file, and shows a
+/// summary of the matches in each file.  Moving past the last match in a
+/// file continues to the next file with a match.
+pub(crate) fn search_all_files(options: Rc<Cell<SearchOptions>>) -> Prompt {
+    Prompt::new(
+        "search",
+        "Search all files:",
+        Box::new(
+            |_screen: &mut Screen, value: &str| -> Result<Option<Action>, Error> {
+                if value.is_empty() {
+                    return Ok(Some(Action::Render));
+                }
+                Ok(Some(Action::SearchAllFiles(value.to_string())))
+            },
+        ),
+    )
+    .with_options(options)
+}
+
+/// Filter the file (Shortcuts: '&', '*')
+///
+/// Prompts the user for a pattern, and adds it to the filter.  If `exclude`
+/// is false, only the lines of the file that match the pattern are shown.
+/// If `exclude` is true, the lines that match the pattern are hidden.  The
+/// search options can be changed while the prompt is open.
+pub(crate) fn filter(
+    exclude: bool,
+    options: Rc<Cell<SearchOptions>>,
+    event_sender: EventSender,
+) -> Prompt {
+    Prompt::new(
+        "filter",
+        if exclude { "Filter out:" } else { "Filter:" },
+        Box::new(
+            move |screen: &mut Screen, value: &str| -> Result<Option<Action>, Error> {
+                if !value.is_empty() {
+                    if let Err(e) = screen.push_filter(value, exclude, event_sender.clone()) {
+                        screen.error = Some(e.to_string());
+                    }
+                }
+                Ok(Some(Action::Render))
+            },
+        ),
+    )
+    .with_options(options)
+}
+
+/// Highlight a pattern (Shortcut: 'H')
+///
+/// Prompts the user for a pattern, and highlights it wherever it appears in
+/// the file.  The search options can be changed while the prompt is open.
+pub(crate) fn highlight(options: Rc<Cell<SearchOptions>>) -> Prompt {
+    Prompt::new(
+        "highlight",
+        "Highlight:",
+        Box::new(
+            move |screen: &mut Screen, value: &str| -> Result<Option<Action>, Error> {
+                if !value.is_empty() {
+                    if let Err(e) = screen.add_highlight(value) {
+                        screen.error = Some(e.to_string());
+                    }
+                }
+                Ok(Some(Action::Render))
+            },
+        ),
+    )
+    .with_options(options)
+}
+
+/// Save the file (Shortcut: 's')
+///
+/// Prompts the user for a path, and saves the content of the file to it.  The
+/// save options can be changed while the prompt is open.  If the path already
+/// exists, asks the user to confirm before overwriting it.
+pub(crate) fn save_file(options: Rc<Cell<SaveOptions>>, event_sender: EventSender) -> Prompt {
+    Prompt::new(
+        "save",
+        "Save to file:",
+        Box::new({
+            let options = options.clone();
+            move |screen: &mut Screen, value: &str| -> Result<Option<Action>, Error> {
+                if value.is_empty() {
+                    return Ok(Some(Action::Render));
+                }
+                let path = PathBuf::from(value);
+                match save::check_destination(&screen.file, &path) {
+                    Ok(false) => start_save(screen, path, options.get(), event_sender.clone()),
+                    Ok(true) => screen.set_prompt(confirm_overwrite(
+                        path,
+                        options.get(),
+                        event_sender.clone(),
+                    )),
+                    Err(e) => screen.error = Some(format!("{:#}", e)),
+                }
+                Ok(Some(Action::Render))
+            }
+        }),
+    )
+    .with_options(options)
+}
+
+/// Asks the user whether to overwrite an existing file when saving.
+fn confirm_overwrite(path: PathBuf, options: SaveOptions, event_sender: EventSender) -> Prompt {
+    Prompt::new(
+        "overwrite",
+        &format!("{} exists.  Overwrite it? (y/n)", path.to_string_lossy()),
+        Box::new(
+            move |screen: &mut Screen, value: &str| -> Result<Option<Action>, Error> {
+                if value == "y" || value == "Y" {
+                    start_save(screen, path.clone(), options, event_sender.clone());
+                }
+                Ok(Some(Action::Render))
+            },
+        ),
+    )
+    .with_single_key()
+}
+
+/// Start saving the file to `path`, reporting any error on the screen.
+fn start_save(screen: &mut Screen, path: PathBuf, options: SaveOptions, event_sender: EventSender) {
+    match save::save_file(&screen.file, path, options, event_sender) {
+        Ok(saving) => screen.add_save(saving),
+        Err(e) => screen.error = Some(format!("{:#}", e)),
+    }
+}
+
+/// Pipe lines to a command (Shortcut: '|')
+///
+/// Prompts the user for the lines to pipe: the lines from a mark to the
+/// screen, `.` for the lines on the screen, `/` for the lines that match the
+/// current search, or `%` for the whole file.  Then prompts the user for a
+/// shell command to pipe them to.  The output of the command is shown in a new
+/// screen.
+pub(crate) fn pipe() -> Prompt {
+    Prompt::new(
+        "pipe-range",
+        "Pipe lines from (mark, . screen, / matches, % file):",
+        Box::new(
+            |screen: &mut Screen, value: &str| -> Result<Option<Action>, Error> {
+                let range = match value.chars().next() {
+                    Some('.') => PipeRange::Screen,
+                    Some('/') => PipeRange::Matches,
+                    Some('%') => PipeRange::File,
+                    Some(mark) => PipeRange::Mark(mark),
+                    None => return Ok(Some(Action::Render)),
+                };
+                match screen.pipe_lines(range) {
+                    Ok(lines) => *screen.prompt() = Some(pipe_command(lines)),
+                    Err(e) => screen.error = Some(e.to_string()),
+                }
+                Ok(Some(Action::Render))
+            },
+        ),
+    )
+    .with_single_key()
+}
+
+/// Prompts the user for a shell command to pipe the given file lines to.
+fn pipe_command(lines: FileLines) -> Prompt {
+    let label = match lines.len() {
+        1 => String::from("Pipe 1 line to:"),
+        count => format!("Pipe {} lines to:", count),
+    };
+    let mut lines = Some(lines);
+    Prompt::new(
+        "pipe",
+        &label,
+        Box::new(
+            move |screen: &mut Screen, value: &str| -> Result<Option<Action>, Error> {
+                match lines.take() {
+                    Some(lines) if !value.is_empty() => {
+                        let input = CommandInput::Lines(screen.file.clone(), lines);
+                        Ok(Some(Action::PipeCommand(value.to_string(), input)))
+                    }
+                    _ => Ok(Some(Action::Render)),
+                }
+            },
+        ),
+    )
+}
+
+/// Edit a temporary copy of the file (Shortcut: 'v')
+///
+/// Files that are not on disk can't be opened in an editor directly.  Asks the
+/// user whether to save the loaded content to a temporary file and edit that
+/// instead.
+pub(crate) fn edit_temporary_file() -> Prompt {
+    Prompt::new(
+        "edit-temporary",
+        "Not a file on disk.  Edit a temporary copy? (y/n)",
+        Box::new(
+            |screen: &mut Screen, value: &str| -> Result<Option<Action>, Error> {
+                if value != "y" && value != "Y" {
+                    return Ok(Some(Action::Render));
+                }
+                match screen.save_temporary_file() {
+                    Ok(path) => Ok(Some(Action::OpenEditor(path, screen.top_file_line() + 1))),
+                    Err(e) => {
+                        screen.error = Some(format!("{:#}", e));
+                        Ok(Some(Action::Render))
+                    }
+                }
+            },
+        ),
+    )
+    .with_single_key()
+}
+
+/// Run a shell command (Shortcut: '!')
+///
+/// Prompts the user for a shell command, and runs it with the terminal
+/// restored.  Waits for a key press before returning to the pager.
+pub(crate) fn shell_command() -> Prompt {
+    Prompt::new(
+        "shell",
+        "!",
+        Box::new(
+            |_screen: &mut Screen, value: &str| -> Result<Option<Action>, Error> {
+                if value.is_empty() {
+                    return Ok(Some(Action::Render));
+                }
+                Ok(Some(Action::RunShellCommand(value.to_string())))
+            },
+        ),
+    )
+}
+
+/// Send input to a command (Shortcut: 'i')
+///
+/// Prompts the user for lines of input, and writes each one to the standard
+/// input of the command whose output is being displayed.  The prompt stays
+/// open for the next line until it is cancelled.  Pressing Ctrl-D on an
+/// empty line closes the command's input.
+pub(crate) fn command_input() -> Prompt {
+    Prompt::new(
+        "input",
+        "Input to command:",
+        Box::new(
+            |screen: &mut Screen, value: &str| -> Result<Option<Action>, Error> {
+                match screen.write_command_input(value) {
+                    Ok(()) => screen.set_prompt(command_input()),
+                    Err(e) => screen.error = Some(e.to_string()),
+                }
+                Ok(Some(Action::Render))
             },
         ),
     )
+    .with_end_of_input(Box::new(
+        |screen: &mut Screen| -> Result<Option<Action>, Error> {
+            screen.close_command_input();
+            Ok(Some(Action::Render))
+        },
+    ))
+    .without_saved_history()
+}
+
+/// Open a file (Shortcut: 'e')
+///
+/// Prompts the user for the path of a file to open in a new screen.  Pressing
+/// Tab completes the path.
+pub(crate) fn open_file() -> Prompt {
+    Prompt::new(
+        "open",
+        "Open file:",
+        Box::new(
+            |_screen: &mut Screen, value: &str| -> Result<Option<Action>, Error> {
+                if value.is_empty() {
+                    return Ok(Some(Action::Render));
+                }
+                Ok(Some(Action::OpenFile(PathBuf::from(value))))
+            },
+        ),
+    )
+    .with_completion(Box::new(complete_path))
+}
+
+/// Complete a path to a file, as far as is unambiguous.  Directories are
+/// completed with a trailing separator.
+fn complete_path(value: &str) -> Option<String> {
+    let (dir, prefix) = match value.rfind('/') {
+        Some(index) => value.split_at(index + 1),
+        None => ("", value),
+    };
+    let entries = fs::read_dir(if dir.is_empty() { "." } else { dir }).ok()?;
+    let mut common: Option<String> = None;
+    for entry in entries.filter_map(|entry| entry.ok()) {
+        let mut name = match entry.file_name().into_string() {
+            Ok(name) => name,
+            Err(_) => continue,
+        };
+        if !name.starts_with(prefix) || (name.starts_with('.') && !prefix.starts_with('.')) {
+            continue;
+        }
+        if entry.file_type().map(|t| t.is_dir()).unwrap_or(false) {
+            name.push('/');
+        }
+        common = Some(match common {
+            None => name,
+            Some(common) => common
+                .chars()
+                .zip(name.chars())
+                .take_while(|(a, b)| a == b)
+                .map(|(a, _)| a)
+                .collect(),
+        });
+    }
+    common
+        .filter(|common| common.len() > prefix.len())
+        .map(|common| format!("{}{}", dir, common))
+}
+
+/// Go to a file (Shortcut: 'l')
+///
+/// Prompts the user for the number of a file to switch to.  This is shown
+/// with the list of open files.
+pub(crate) fn select_file() -> Prompt {
+    Prompt::new(
+        "select-file",
+        "Go to file:",
+        Box::new(
+            |screen: &mut Screen, value: &str| -> Result<Option<Action>, Error> {
+                if value.is_empty() {
+                    return Ok(Some(Action::Render));
+                }
+                match str::parse::<usize>(value) {
+                    Ok(number) => Ok(Some(Action::SelectFile(number))),
+                    Err(e) => {
+                        screen.error = Some(e.to_string());
+                        Ok(Some(Action::Render))
+                    }
+                }
+            },
+        ),
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_goto() {
+        use GotoPosition::{Line, Offset};
+
+        // Line numbers are 1-based, and clamped to the file.
+        assert_eq!(parse_goto("1", 100, 50), Ok(Line(0)));
+        assert_eq!(parse_goto("42", 100, 50), Ok(Line(41)));
+        assert_eq!(parse_goto("0", 100, 50), Ok(Line(0)));
+        assert_eq!(parse_goto("1000", 100, 50), Ok(Line(99)));
+        assert_eq!(parse_goto("$", 100, 50), Ok(Line(99)));
+
+        // Relative lines are from the top of the screen.
+        assert_eq!(parse_goto("+10", 100, 50), Ok(Line(60)));
+        assert_eq!(parse_goto("-10", 100, 50), Ok(Line(40)));
+        assert_eq!(parse_goto("+1000", 100, 50), Ok(Line(99)));
+        assert_eq!(parse_goto("-1000", 100, 50), Ok(Line(0)));
+
+        // Percentages are of the last line, and negative ones are from the
+        // end of the file.
+        assert_eq!(parse_goto("0%", 101, 50), Ok(Line(0)));
+        assert_eq!(parse_goto("50%", 101, 50), Ok(Line(50)));
+        assert_eq!(parse_goto("100%", 101, 50), Ok(Line(100)));
+        assert_eq!(parse_goto("150%", 101, 50), Ok(Line(100)));
+        assert_eq!(parse_goto("-25%", 101, 50), Ok(Line(75)));
+        assert_eq!(parse_goto("-100%", 101, 50), Ok(Line(0)));
+
+        assert_eq!(parse_goto("b1234", 100, 50), Ok(Offset(1234)));
+
+        assert_eq!(parse_goto("$", 0, 0), Ok(Line(0)));
+        assert!(parse_goto("x", 100, 50).is_err());
+        assert!(parse_goto("x%", 100, 50).is_err());
+        assert!(parse_goto("b-1", 100, 50).is_err());
+        assert!(parse_goto("+", 100, 50).is_err());
+    }
+
+    #[test]
+    fn test_complete_path() {
+        let dir = tempfile::tempdir().unwrap();
+        for name in ["apple.txt", "apricot.txt", "banana/.hidden", "banana/seed"] {
+            let path = dir.path().join(name);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, "").unwrap();
+        }
+        let dir = dir.path().to_str().unwrap();
+        let complete = |value: &str| complete_path(&format!("{}/{}", dir, value));
+        let completed = |value: &str| Some(format!("{}/{}", dir, value));
+
+        // Completes as far as the common prefix of the matching names.
+        assert_eq!(complete("a"), completed("ap"));
+        assert_eq!(complete("apr"), completed("apricot.txt"));
+        assert_eq!(complete("apple.txt"), None);
+        assert_eq!(complete("c"), None);
+
+        // Directories are completed with a trailing separator.
+        assert_eq!(complete("b"), completed("banana/"));
+
+        // Hidden files are only completed if the prefix starts with a dot.
+        assert_eq!(complete("banana/"), completed("banana/seed"));
+        assert_eq!(complete("banana/."), completed("banana/.hidden"));
+
+        assert_eq!(complete("missing/a"), None);
+    }
 }
@@ -3,7 +3,7 @@
 use std::sync::Arc;
 use std::time::Duration;
 
-use anyhow::Result;
+use anyhow::{bail, Result};
 use serde::Deserialize;
 
 use crate::bindings::Keymap;
@@ -119,6 +119,106 @@ impl Default for WrappingMode {
     }
 }
 
+/// Specify how search patterns are interpreted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+pub enum SearchMode {
+    /// Patterns are regular expressions.
+    #[serde(rename = "regex")]
+    Regex,
+    /// Patterns are matched literally.
+    #[serde(rename = "literal")]
+    Literal,
+}
+
+impl SearchMode {
+    pub(crate) fn next_mode(self) -> SearchMode {
+        match self {
+            SearchMode::Regex => SearchMode::Literal,
+            SearchMode::Literal => SearchMode::Regex,
+        }
+    }
+}
+
+impl Default for SearchMode {
+    fn default() -> Self {
+        Self::Regex
+    }
+}
+
+/// Specify whether searches are case sensitive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+pub enum SearchCase {
+    /// Searches are case sensitive.
+    #[serde(rename = "sensitive")]
+    Sensitive,
+    /// Searches are case insensitive.
+    #[serde(rename = "insensitive")]
+    Insensitive,
+    /// Searches are case insensitive, unless the pattern contains an
+    /// uppercase character.
+    #[serde(rename = "smart")]
+    Smart,
+}
+
+impl SearchCase {
+    pub(crate) fn next_mode(self) -> SearchCase {
+        match self {
+            SearchCase::Sensitive => SearchCase::Insensitive,
+            SearchCase::Insensitive => SearchCase::Smart,
+            SearchCase::Smart => SearchCase::Sensitive,
+        }
+    }
+}
+
+impl Default for SearchCase {
+    fn default() -> Self {
+        Self::Sensitive
+    }
+}
+
+/// Specify what happens to commands that are still running when the pager
+/// quits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+pub enum QuitPolicy {
+    /// Commands are sent `SIGTERM`.
+    #[serde(rename = "kill")]
+    Kill,
+    /// The pager waits for commands to exit.
+    #[serde(rename = "wait")]
+    Wait,
+    /// Commands are left running.
+    #[serde(rename = "detach")]
+    Detach,
+}
+
+impl Default for QuitPolicy {
+    fn default() -> Self {
+        Self::Detach
+    }
+}
+
+impl QuitPolicy {
+    fn from_str(value: &str) -> Option<QuitPolicy> {
+        match value.to_ascii_lowercase().as_ref() {
+            "kill" => Some(QuitPolicy::Kill),
+            "wait" => Some(QuitPolicy::Wait),
+            "detach" => Some(QuitPolicy::Detach),
+            _ => None,
+        }
+    }
+}
+
+/// A pattern to highlight in every file.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct HighlightConfig {
+    /// The pattern to highlight.
+    pub pattern: String,
+
+    /// The name of the color to highlight the pattern in.  If not specified,
+    /// a color is chosen automatically.
+    pub color: Option<String>,
+}
+
 /// Keymap Configuration
 #[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
 #[serde(from = "&str")]
@@ -169,6 +269,42 @@ pub struct Config {
 
     /// Specify the name of the default key map.
     pub keymap: KeymapConfig,
+
+    /// Specify how search patterns are interpreted by default.
+    pub search_mode: SearchMode,
+
+    /// Specify whether searches are case sensitive by default.
+    pub search_case: SearchCase,
+
+    /// Specify whether to search as the search term is typed.
+    pub incremental_search: bool,
+
+    /// Specify patterns to highlight in every file.
+    pub highlight: Vec<HighlightConfig>,
+
+    /// Specify whether to restore the position, presentation and search of
+    /// files on disk from the last time they were viewed.
+    pub restore_session: bool,
+
+    /// Specify a command to preprocess files before they are displayed.
+    /// `%s` is replaced by the name of the file, already quoted for the
+    /// shell, so commands written for `less` that quote it as `'%s'` need the
+    /// quotes removed.  If the command produces no output, the file is
+    /// displayed as normal.
+    pub preprocessor: Option<String>,
+
+    /// Specify whether subprocesses are run with their output and error
+    /// streams attached to pseudo-terminals.
+    pub pty: bool,
+
+    /// Specify whether subprocesses are run with their standard input kept
+    /// open, so that input can be forwarded to them from the pager.
+    /// Otherwise their standard input is empty.
+    pub forward_input: bool,
+
+    /// Specify what happens to commands that are still running when the
+    /// pager quits.
+    pub quit_policy: QuitPolicy,
 }
 
 impl Default for Config {
@@ -179,6 +315,15 @@ impl Default for Config {
             read_ahead_lines: crate::file::DEFAULT_NEEDED_LINES,
             wrapping_mode: Default::default(),
             keymap: Default::default(),
+            search_mode: Default::default(),
+            search_case: Default::default(),
+            incremental_search: false,
+            highlight: Vec::new(),
+            restore_session: true,
+            preprocessor: None,
+            pty: false,
+            forward_input: false,
+            quit_policy: Default::default(),
         }
     }
 }
@@ -218,10 +363,47 @@ impl Config {
                 self.read_ahead_lines = n;
             }
         }
+        if let Ok(s) = var("SP_INCREMENTAL_SEARCH") {
+            if let Some(b) = parse_bool(&s) {
+                self.incremental_search = b;
+            }
+        }
+        if let Ok(s) = var("SP_RESTORE_SESSION") {
+            if let Some(b) = parse_bool(&s) {
+                self.restore_session = b;
+            }
+        }
+        if let Ok(s) = var("SP_PTY") {
+            if let Some(b) = parse_bool(&s) {
+                self.pty = b;
+            }
+        }
+        if let Ok(s) = var("SP_FORWARD_INPUT") {
+            if let Some(b) = parse_bool(&s) {
+                self.forward_input = b;
+            }
+        }
+        if let Ok(s) = var("SP_QUIT_POLICY") {
+            if let Some(policy) = QuitPolicy::from_str(&s) {
+                self.quit_policy = policy;
+            }
+        }
+        if let Ok(s) = var("SP_PREPROCESSOR") {
+            self.preprocessor = Some(s).filter(|s| !s.is_empty());
+        }
         self
     }
 }
 
+/// Parse an interval in seconds, which may be fractional, such as the watch
+/// interval given on the command line.
+pub fn parse_interval(seconds: &str) -> Result<Duration> {
+    match seconds.parse::<f64>() {
+        Ok(seconds) if seconds > 0.0 && seconds.is_finite() => Ok(Duration::from_secs_f64(seconds)),
+        _ => bail!("invalid interval: {}", seconds),
+    }
+}
+
 fn parse_bool(value: &str) -> Option<bool> {
     match value.to_ascii_lowercase().as_ref() {
         "1" | "yes" | "true" | "on" | "always" => Some(true),
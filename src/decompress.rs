@@ -0,0 +1,188 @@
+//! Decompression.
+//!
+//! Compressed files are detected by the magic bytes at the start of their
+//! content, and decompressed as they are streamed in.
+//!
+//! Support for *zstd* and *xz* requires C libraries, so each is behind a Cargo
+//! feature.
+use flate2::read::MultiGzDecoder;
+use std::io::{Cursor, Read};
+#[cfg(feature = "xz-decompression")]
+use xz2::read::XzDecoder;
+#[cfg(feature = "zstd-decompression")]
+use zstd::stream::read::Decoder as ZstdDecoder;
+
+/// The number of bytes needed to detect the compression type of a file.
+pub(crate) const MAGIC_LENGTH: usize = 6;
+
+/// A type of compression.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub(crate) enum Compression {
+    Gzip,
+    #[cfg(feature = "zstd-decompression")]
+    Zstd,
+    #[cfg(feature = "xz-decompression")]
+    Xz,
+}
+
+impl Compression {
+    /// Detect the compression type from the first bytes of a file.  Returns
+    /// `None` if the data does not look compressed with a supported type.
+    pub(crate) fn detect(data: &[u8]) -> Option<Compression> {
+        if data.starts_with(b"\x1f\x8b") {
+            return Some(Compression::Gzip);
+        }
+        #[cfg(feature = "zstd-decompression")]
+        if data.starts_with(b"\x28\xb5\x2f\xfd") {
+            return Some(Compression::Zstd);
+        }
+        #[cfg(feature = "xz-decompression")]
+        if data.starts_with(b"\xfd7zXZ\x00") {
+            return Some(Compression::Xz);
+        }
+        None
+    }
+
+    /// The name of the compression type, for display in the ruler.
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            Compression::Gzip => "gzip",
+            #[cfg(feature = "zstd-decompression")]
+            Compression::Zstd => "zstd",
+            #[cfg(feature = "xz-decompression")]
+            Compression::Xz => "xz",
+        }
+    }
+
+    /// Wrap `input` in a reader that decompresses it.
+    pub(crate) fn decoder(
+        self,
+        input: impl Read + Send + 'static,
+    ) -> std::io::Result<Box<dyn Read + Send>> {
+        Ok(match self {
+            Compression::Gzip => Box::new(MultiGzDecoder::new(input)),
+            #[cfg(feature = "zstd-decompression")]
+            Compression::Zstd => Box::new(ZstdDecoder::new(input)?),
+            #[cfg(feature = "xz-decompression")]
+            Compression::Xz => Box::new(XzDecoder::new_multi_decoder(input)),
+        })
+    }
+}
+
+/// The state of an `AutoDecoder`.
+enum DecoderState<R> {
+    /// The input hasn't been read from yet.
+    Pending(R),
+
+    /// The input is being read, through a decoder if it is compressed.
+    Reading(Box<dyn Read + Send>),
+
+    /// Detecting the compression type failed.
+    Failed,
+}
+
+/// A reader that detects whether its input is compressed the first time it
+/// is read from, and decompresses it if it is.
+///
+/// Reading the magic bytes from a pipe blocks until they are written, so this
+/// allows detection to happen on the thread that loads the stream.
+pub(crate) struct AutoDecoder<R> {
+    state: DecoderState<R>,
+
+    /// Called with the compression type once it has been detected.
+    detected: Option<Box<dyn FnOnce(Compression) + Send>>,
+}
+
+impl<R: Read + Send + 'static> AutoDecoder<R> {
+    pub(crate) fn new(input: R, detected: Box<dyn FnOnce(Compression) + Send>) -> AutoDecoder<R> {
+        AutoDecoder {
+            state: DecoderState::Pending(input),
+            detected: Some(detected),
+        }
+    }
+
+    /// Read the magic bytes from `input` and pick the reader to use for the
+    /// rest of the data.
+    fn start(&mut self, mut input: R) -> std::io::Result<Box<dyn Read + Send>> {
+        let mut magic = Vec::with_capacity(MAGIC_LENGTH);
+        (&mut input)
+            .take(MAGIC_LENGTH as u64)
+            .read_to_end(&mut magic)?;
+        let compression = Compression::detect(&magic);
+        let input = Cursor::new(magic).chain(input);
+        match compression {
+            Some(compression) => {
+                if let Some(detected) = self.detected.take() {
+                    detected(compression);
+                }
+                compression.decoder(input)
+            }
+            None => Ok(Box::new(input)),
+        }
+    }
+}
+
+impl<R: Read + Send + 'static> Read for AutoDecoder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if let DecoderState::Pending(_) = self.state {
+            if let DecoderState::Pending(input) =
+                std::mem::replace(&mut self.state, DecoderState::Failed)
+            {
+                self.state = DecoderState::Reading(self.start(input)?);
+            }
+        }
+        match self.state {
+            DecoderState::Reading(ref mut reader) => reader.read(buf),
+            DecoderState::Pending(_) | DecoderState::Failed => Ok(0),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_detect() {
+        assert_eq!(
+            Compression::detect(b"\x1f\x8b\x08\x00\x00\x00"),
+            Some(Compression::Gzip)
+        );
+        #[cfg(feature = "zstd-decompression")]
+        assert_eq!(
+            Compression::detect(b"\x28\xb5\x2f\xfd\x04\x58"),
+            Some(Compression::Zstd)
+        );
+        #[cfg(feature = "xz-decompression")]
+        assert_eq!(Compression::detect(b"\xfd7zXZ\x00"), Some(Compression::Xz));
+        assert_eq!(Compression::detect(b"\xfd7zXZ"), None);
+        assert_eq!(Compression::detect(b"plain text"), None);
+        assert_eq!(Compression::detect(b""), None);
+    }
+
+    #[test]
+    fn test_auto_decoder() {
+        use flate2::write::GzEncoder;
+        use std::io::Write;
+
+        let mut encoder = GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(b"compressed\n").unwrap();
+        let compressed = encoder.finish().unwrap();
+        let mut output = String::new();
+        let (sender, receiver) = std::sync::mpsc::channel();
+        AutoDecoder::new(
+            Cursor::new(compressed),
+            Box::new(move |compression| sender.send(compression).unwrap()),
+        )
+        .read_to_string(&mut output)
+        .unwrap();
+        assert_eq!(output, "compressed\n");
+        assert_eq!(receiver.try_recv(), Ok(Compression::Gzip));
+
+        let mut output = String::new();
+        AutoDecoder::new(Cursor::new(b"plain"), Box::new(|_| panic!()))
+            .read_to_string(&mut output)
+            .unwrap();
+        assert_eq!(output, "plain");
+    }
+}
@@ -1,7 +1,11 @@
 //! Manage the Display.
-use anyhow::Error;
+use anyhow::{bail, Error};
 use scopeguard::guard;
-use std::sync::atomic::{AtomicUsize, Ordering};
+use std::cmp::min;
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::sync::Arc;
 use std::time::Duration;
 use termwiz::caps::Capabilities as TermCapabilities;
@@ -14,14 +18,17 @@ use termwiz::terminal::Terminal;
 use vec_map::VecMap;
 
 use crate::command;
-use crate::config::Config;
+use crate::config::{Config, QuitPolicy};
 use crate::direct;
-use crate::event::{Event, EventStream, UniqueInstance};
-use crate::file::File;
+use crate::editor;
+use crate::event::{Event, EventSender, EventStream, UniqueInstance};
+use crate::file::{CommandInput, File, Signal};
 use crate::help::help_text;
 use crate::progress::Progress;
 use crate::screen::Screen;
-use crate::search::SearchKind;
+use crate::search::{MatchMotion, Search, SearchKind, SearchOptions};
+use crate::util;
+use crate::util::number_width;
 
 /// Capabilities of the terminal that we care about.
 #[derive(Default)]
@@ -71,12 +78,55 @@ pub(crate) enum Action {
     /// Move to the previous file.
     PreviousFile,
 
+    /// Show the list of open files.
+    ShowFileList,
+
+    /// Move to the file with the given number in the file list.
+    SelectFile(usize),
+
+    /// Open a file from disk in a new screen.
+    OpenFile(PathBuf),
+
+    /// Close the current file.
+    CloseFile,
+
+    /// Run the command whose output is displayed in the current file again.
+    RerunCommand,
+
     /// Show the help screen.
     ShowHelp,
 
+    /// Show the list of marks for the current screen.
+    ShowMarks,
+
+    /// Search for a pattern in all files.
+    SearchAllFiles(String),
+
+    /// Show the summary of the search across all files.
+    ShowSearchSummary,
+
+    /// Move to the first match in the next file that has matches.
+    NextMatchFile,
+
+    /// Move to the last match in the previous file that has matches.
+    PreviousMatchFile,
+
     /// Clear the overlay.
     ClearOverlay,
 
+    /// Run a shell command with the given input, and show its output in a
+    /// new screen.
+    PipeCommand(String, CommandInput),
+
+    /// Open a file in the user's editor at the given line.
+    OpenEditor(PathBuf, usize),
+
+    /// Suspend the process until it is resumed by job control.
+    Suspend,
+
+    /// Run a shell command with the terminal restored.
+    RunShellCommand(String),
+
     /// Close the program.
     Quit,
 }
@@ -95,10 +145,17 @@ struct Screens {
     /// The file index of the overlay.  While overlays aren't part of the
     /// screens vector, we still need a file index so that the file loader can
     /// report loading completion and the search thread can report search
-    /// matches.  Use an index starting after the loaded files for this purpose.
-    /// Each time a new overlay is added, this index is incremented, so that
-    /// each overlay gets a unique index.
+    /// matches.  Each time a new overlay is added, it is given the next unused
+    /// index, so that each overlay gets a unique index.
     overlay_index: usize,
+
+    /// The next unused file index.  Files for screens and overlays that are
+    /// added after startup are given indexes from here.
+    next_index: usize,
+
+    /// The overlay index of the summary of the search across all files, if
+    /// it has been shown.
+    summary_index: Option<usize>,
 }
 
 impl Screens {
@@ -108,12 +165,13 @@ impl Screens {
         mut error_files: VecMap<File>,
         progress: Option<Progress>,
         config: Arc<Config>,
+        event_sender: &EventSender,
     ) -> Result<Screens, Error> {
         let count = files.len();
         let mut screens = Vec::new();
         for file in files.into_iter() {
             let index = file.index();
-            let mut screen = Screen::new(file, config.clone())?;
+            let mut screen = Screen::new(file, config.clone(), event_sender)?;
             screen.set_progress(progress.clone());
             screen.set_error_file(error_files.remove(index));
             screens.push(screen);
@@ -123,6 +181,8 @@ impl Screens {
             overlay: None,
             current_index: 0,
             overlay_index: count,
+            next_index: count + 1,
+            summary_index: None,
         })
     }
 
@@ -139,20 +199,409 @@ impl Screens {
     fn is_current_index(&self, index: usize) -> bool {
         match self.overlay {
             Some(_) => index == self.overlay_index,
-            None => index == self.current_index,
+            None => self.screens[self.current_index].file.index() == index,
         }
     }
 
+    /// Get the position in the screens vector of the screen for the file with
+    /// the given index.
+    fn position(&self, index: usize) -> Option<usize> {
+        self.screens
+            .iter()
+            .position(|screen| screen.file.index() == index)
+    }
+
     /// Get the screen with the given index.
     fn get(&mut self, index: usize) -> Option<&mut Screen> {
-        if index == self.overlay_index {
+        if let Some(position) = self.position(index) {
+            Some(&mut self.screens[position])
+        } else if index == self.overlay_index {
             self.overlay.as_mut()
-        } else if index < self.screens.len() {
-            Some(&mut self.screens[index])
         } else {
             None
         }
     }
+
+    /// Replace the overlay with a new screen showing static text.
+    fn show_overlay(
+        &mut self,
+        title: &str,
+        text: String,
+        event_sender: &EventSender,
+        config: Arc<Config>,
+    ) -> Result<&mut Screen, Error> {
+        let overlay_index = self.next_index;
+        self.next_index += 1;
+        let screen = Screen::new(
+            File::new_static(
+                overlay_index,
+                title,
+                text.into_bytes(),
+                event_sender.clone(),
+            )?,
+            config,
+            event_sender,
+        )?;
+        self.overlay = Some(screen);
+        self.overlay_index = overlay_index;
+        Ok(self.overlay.as_mut().unwrap())
+    }
+
+    /// Returns a list of the open files, for display in an overlay.
+    fn file_list(&self) -> String {
+        let width = number_width(self.screens.len());
+        let mut text = String::from("Files:\n\n");
+        for (index, screen) in self.screens.iter().enumerate() {
+            let file = &screen.file;
+            let mut details = format!("{} lines", file.lines());
+            if !file.loaded() {
+                details.push_str(", loading");
+            }
+            let info = file.info();
+            if !info.is_empty() {
+                details.push_str(", ");
+                details.push_str(&info);
+            }
+            let marker = if index == self.current_index {
+                '*'
+            } else {
+                ' '
+            };
+            text.push_str(&format!(
+                "{} {:>width$}  {}  ({})\n",
+                marker,
+                index + 1,
+                file.title(),
+                details,
+                width = width,
+            ));
+        }
+        text
+    }
+
+    /// Open a file from disk, and add a screen for it.  The new screen
+    /// becomes the current screen.
+    fn open_file(
+        &mut self,
+        path: &Path,
+        event_sender: &EventSender,
+        config: Arc<Config>,
+    ) -> Result<(), Error> {
+        let index = self.next_index;
+        let file = match config.preprocessor {
+            Some(ref preprocessor) => {
+                File::new_preprocessed(index, path.as_os_str(), preprocessor, event_sender.clone())?
+            }
+            None => File::new_file(index, path.as_os_str(), event_sender.clone())?,
+        };
+        self.next_index += 1;
+        let screen = Screen::new(file, config, event_sender)?;
+        self.screens.push(screen);
+        self.current_index = self.screens.len() - 1;
+        self.overlay = None;
+        Ok(())
+    }
+
+    /// Close the current screen.  The last screen can't be closed.
+    fn close_current(&mut self) -> Result<(), Error> {
+        if self.screens.len() <= 1 {
+            bail!("Can't close the only file");
+        }
+        let screen = self.screens.remove(self.current_index);
+        let _ = screen.save_session();
+        self.current_index = min(self.current_index, self.screens.len() - 1);
+        self.overlay = None;
+        Ok(())
+    }
+
+    /// Run a shell command with `input` as its standard input, and add
+    /// screens for its output, error and the two combined.  The screen for
+    /// the output becomes the current screen.
+    fn add_command(
+        &mut self,
+        command: &str,
+        input: CommandInput,
+        event_sender: &EventSender,
+        config: Arc<Config>,
+    ) -> Result<(), Error> {
+        let index = self.next_index;
+        let (out_file, err_file, combined_file) = File::new_command(
+            index,
+            &util::shell(),
+            [OsStr::new("-c"), OsStr::new(command)],
+            input,
+            None,
+            &format!("| {}", command),
+            event_sender.clone(),
+        )?;
+        self.next_index += 3;
+        let mut out_screen = Screen::new(out_file, config.clone(), event_sender)?;
+        out_screen.set_error_file(Some(err_file.clone()));
+        let err_screen = Screen::new(err_file, config.clone(), event_sender)?;
+        let combined_screen = Screen::new(combined_file, config, event_sender)?;
+        self.screens.push(out_screen);
+        self.screens.push(err_screen);
+        self.screens.push(combined_screen);
+        self.current_index = self.screens.len() - 3;
+        self.overlay = None;
+        Ok(())
+    }
+
+    /// Run the command whose output or error is in the file with index
+    /// `index` again, replacing its files in place.  `size` is the current
+    /// size of the screen.
+    fn rerun_command(
+        &mut self,
+        index: usize,
+        mark_changes: bool,
+        size: (usize, usize),
+        event_sender: &EventSender,
+    ) -> Result<(), Error> {
+        let file = match self.get(index) {
+            Some(screen) => screen.file.clone(),
+            None => return Ok(()),
+        };
+        let (out_file, err_file, combined_file) = file.rerun(size, event_sender.clone())?;
+        for screen in self.screens.iter_mut() {
+            if screen.file.index() == out_file.index() {
+                screen.replace_file(out_file.clone(), mark_changes, event_sender)?;
+                screen.set_error_file(Some(err_file.clone()));
+            } else if screen.file.index() == err_file.index() {
+                screen.replace_file(err_file.clone(), mark_changes, event_sender)?;
+            } else if screen.file.index() == combined_file.index() {
+                screen.replace_file(combined_file.clone(), mark_changes, event_sender)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// True if the overlay is showing the summary of the search across all
+    /// files.
+    fn showing_search_summary(&self) -> bool {
+        self.overlay.is_some() && self.summary_index == Some(self.overlay_index)
+    }
+
+    /// Start searching for a pattern in all files.
+    fn search_all_files(
+        &mut self,
+        pattern: &str,
+        options: SearchOptions,
+        event_sender: &EventSender,
+    ) -> Result<(), Error> {
+        // Check the pattern is valid before replacing any searches.
+        options.regex(pattern)?;
+        for screen in self.screens.iter_mut() {
+            let search = Search::new(
+                &screen.file,
+                pattern,
+                options,
+                SearchKind::First,
+                event_sender.clone(),
+            )?;
+            screen.set_search_all_files(search);
+        }
+        Ok(())
+    }
+
+    /// True if the search of any file is still running.
+    fn searching(&self) -> bool {
+        self.screens.iter().any(|screen| {
+            screen
+                .search()
+                .map(|search| !search.finished())
+                .unwrap_or(false)
+        })
+    }
+
+    /// Update the summary of the search across all files that is being
+    /// shown in the overlay, keeping its position.
+    fn refresh_search_summary(&mut self, event_sender: &EventSender) -> Result<(), Error> {
+        let text = self.search_summary();
+        if let Some(ref mut overlay) = self.overlay {
+            let file = File::new_static(
+                self.overlay_index,
+                "SEARCH",
+                text.into_bytes(),
+                event_sender.clone(),
+            )?;
+            overlay.replace_file(file, false, event_sender)?;
+        }
+        Ok(())
+    }
+
+    /// Returns the text of the summary of the search across all files.
+    fn search_summary(&self) -> String {
+        let pattern = self
+            .screens
+            .iter()
+            .filter_map(|screen| screen.search())
+            .map(|search| search.pattern())
+            .next()
+            .unwrap_or("");
+        let mut summary = format!("Search for \"{}\" in all files:\n\n", pattern);
+        for (index, screen) in self.screens.iter().enumerate() {
+            if let Some(search) = screen.search().map(Search::progress) {
+                summary.push_str(&format!(
+                    "{:>4}  {:>8} matches on {:>8} lines  {}{}\n",
+                    index + 1,
+                    search.match_count(),
+                    search.matching_line_count(),
+                    screen.file.title(),
+                    if search.finished() {
+                        ""
+                    } else {
+                        " (searching)"
+                    },
+                ));
+            }
+        }
+        summary
+    }
+
+    /// Returns the index of the nearest file after the current file, or
+    /// before it if `forwards` is false, that has search matches.
+    fn match_file(&self, forwards: bool) -> Option<usize> {
+        let has_matches = |index: &usize| {
+            self.screens[*index]
+                .search()
+                .map(|search| search.progress().match_count())
+                .unwrap_or(0)
+                > 0
+        };
+        if forwards {
+            (self.current_index + 1..self.screens.len()).find(has_matches)
+        } else {
+            (0..self.current_index).rev().find(has_matches)
+        }
+    }
+}
+
+/// Suspend the process with job control.  Returns once the process has been
+/// resumed.
+///
+/// The terminal is in raw mode, so the signal is sent to the whole process
+/// group, as the terminal would for the suspend key, so that processes
+/// writing to the pager are suspended too.
+#[cfg(unix)]
+fn suspend() -> Result<(), Error> {
+    // Safety: sending a signal has no memory safety requirements.
+    if unsafe { libc::kill(0, libc::SIGTSTP) } != 0 {
+        return Err(std::io::Error::last_os_error().into());
+    }
+    Ok(())
+}
+
+/// Suspending is only supported with job control.
+#[cfg(not(unix))]
+fn suspend() -> Result<(), Error> {
+    Err(anyhow::anyhow!("Suspend is not supported on this platform"))
+}
+
+/// Restore the terminal to its normal state while `f` runs, so that it can
+/// run interactive programs.  If `wait_for_key` is true, waits for the user to
+/// press a key afterwards, so that they can read the program's output.
+fn with_terminal_restored<T>(
+    term: &mut impl Terminal,
+    alternate_screen: bool,
+    wait_for_key: bool,
+    f: impl FnOnce() -> T,
+) -> Result<T, Error> {
+    term.render(&[
+        Change::CursorShape(CursorShape::Default),
+        Change::AllAttributes(CellAttributes::default()),
+    ])?;
+    if alternate_screen {
+        term.exit_alternate_screen()?;
+    }
+    term.set_cooked_mode()?;
+    term.flush()?;
+    let result = f();
+    term.set_raw_mode()?;
+    if wait_for_key {
+        term.render(&[Change::Text(String::from(
+            "\r\n[Press any key to continue]",
+        ))])?;
+        term.flush()?;
+        loop {
+            if let Some(InputEvent::Key(_)) = term.poll_input(None)? {
+                break;
+            }
+        }
+    }
+    if alternate_screen {
+        term.enter_alternate_screen()?;
+    }
+    Ok(result)
+}
+
+/// Clean up the terminal when exiting.  Most of this should be achieved by
+/// exiting the alternate screen, but just in case it isn't, move to the
+/// bottom of the screen and reset all attributes.
+fn clean_up(term: &mut impl Terminal, overlay_height: usize) -> Result<(), Error> {
+    let size = term.get_screen_size()?;
+    let scroll_count = 1usize.saturating_sub(overlay_height);
+    term.render(&[
+        Change::CursorShape(CursorShape::Default),
+        Change::AllAttributes(CellAttributes::default()),
+        Change::ScrollRegionUp {
+            first_row: 0,
+            region_size: size.rows,
+            scroll_count,
+        },
+        Change::CursorPosition {
+            x: Position::Absolute(0),
+            y: Position::Absolute(size.rows.saturating_sub(overlay_height + scroll_count)),
+        },
+        Change::ClearToEndOfScreen(ColorAttribute::default()),
+    ])?;
+    Ok(())
+}
+
+/// Apply the quit policy to any commands that are still running.
+///
+/// If the pager waits for commands, the terminal is cleaned up and
+/// restored to its normal state first, and a message is shown while
+/// waiting, so that the user can interrupt the wait.  Returns true if the
+/// terminal was cleaned up.
+fn finish_commands(
+    term: &mut impl Terminal,
+    files: &[File],
+    policy: QuitPolicy,
+    overlay_height: usize,
+    alternate_screen: bool,
+) -> Result<bool, Error> {
+    let running: Vec<&File> = files.iter().filter(|file| file.command_running()).collect();
+    match policy {
+        QuitPolicy::Kill => {
+            for file in running {
+                let _ = file.signal_command(Signal::Terminate);
+            }
+        }
+        QuitPolicy::Wait if !running.is_empty() => {
+            let mut titles: Vec<&str> = running
+                .iter()
+                .filter_map(|file| file.command_title())
+                .collect();
+            titles.dedup();
+            clean_up(term, overlay_height)?;
+            if alternate_screen {
+                term.exit_alternate_screen()?;
+            }
+            term.set_cooked_mode()?;
+            let mut changes = Vec::new();
+            for title in titles {
+                changes.push(Change::Text(format!("Waiting for {} to exit\r\n", title)));
+            }
+            term.render(&changes)?;
+            term.flush()?;
+            for file in running {
+                file.wait_command();
+            }
+            return Ok(true);
+        }
+        QuitPolicy::Wait | QuitPolicy::Detach => {}
+    }
+    Ok(false)
 }
 
 /// Start displaying files.
@@ -182,40 +631,26 @@ pub(crate) fn start(
             config.interface_mode,
         )?
     };
-    match outcome {
+    let alternate_screen = match outcome {
         direct::Outcome::RenderComplete | direct::Outcome::Interrupted => return Ok(()),
-        direct::Outcome::RenderIncomplete => (),
-        direct::Outcome::RenderNothing => term.enter_alternate_screen()?,
-    }
+        direct::Outcome::RenderIncomplete => false,
+        direct::Outcome::RenderNothing => {
+            term.enter_alternate_screen()?;
+            true
+        }
+    };
 
     let overlay_height = AtomicUsize::new(0);
+    let cleaned_up = AtomicBool::new(false);
     let mut term = guard(term, |mut term| {
-        // Clean up when exiting.  Most of this should be achieved by exiting
-        // the alternate screen, but just in case it isn't, move to the
-        // bottom of the screen and reset all attributes.
-        let size = term.get_screen_size().unwrap();
-        let overlay_height = overlay_height.load(Ordering::SeqCst);
-        let scroll_count = 1usize.saturating_sub(overlay_height);
-        term.render(&[
-            Change::CursorShape(CursorShape::Default),
-            Change::AllAttributes(CellAttributes::default()),
-            Change::ScrollRegionUp {
-                first_row: 0,
-                region_size: size.rows,
-                scroll_count,
-            },
-            Change::CursorPosition {
-                x: Position::Absolute(0),
-                y: Position::Absolute(size.rows.saturating_sub(overlay_height + scroll_count)),
-            },
-            Change::ClearToEndOfScreen(ColorAttribute::default()),
-        ])
-        .unwrap();
+        if !cleaned_up.load(Ordering::SeqCst) {
+            clean_up(&mut term, overlay_height.load(Ordering::SeqCst)).unwrap();
+        }
     });
     let config = Arc::new(config);
     let caps = Capabilities::new(term_caps);
-    let mut screens = Screens::new(files, error_files, progress, config.clone())?;
     let event_sender = events.sender();
+    let mut screens = Screens::new(files, error_files, progress, config.clone(), &event_sender)?;
     let render_unique = UniqueInstance::new();
     let refresh_unique = UniqueInstance::new();
     {
@@ -227,12 +662,15 @@ pub(crate) fn start(
     }
     loop {
         // Listen for an event or input.  If we are animating, put a timeout on the wait.
-        let timeout = if screens.current().animate() {
+        let timeout = if screens.current().animate()
+            || (screens.showing_search_summary() && screens.searching())
+        {
             Some(Duration::from_millis(100))
         } else {
             None
         };
         let event = events.get(&mut *term, timeout)?;
+        let refresh_summary = event.is_none() && screens.showing_search_summary();
 
         // Dispatch the event and receive an action to take.
         let mut action = {
@@ -240,6 +678,10 @@ pub(crate) fn start(
             screen.maybe_load_more();
 
             match event {
+                None if refresh_summary => {
+                    screens.refresh_search_summary(&event_sender)?;
+                    Some(Action::Refresh)
+                }
                 None => screen.dispatch_animation()?,
                 Some(Event::Render) => {
                     term.render(&screen.render(&caps)?)?;
@@ -258,6 +700,10 @@ pub(crate) fn start(
                     term.render(&screen.render(&caps)?)?;
                     None
                 }
+                Some(Event::RefreshOverlay) => {
+                    screen.refresh_overlay();
+                    Some(Action::Render)
+                }
                 Some(Event::Progress) => {
                     screen.refresh_progress();
                     term.render(&screen.render(&caps)?)?;
@@ -273,11 +719,17 @@ pub(crate) fn start(
                 }
                 Some(Event::Input(InputEvent::Paste(ref text))) => {
                     let width = screen.width();
+                    let search_options = screen.search_options();
                     screen
                         .prompt()
                         .get_or_insert_with(|| {
                             // Assume the user wanted to search for what they're pasting.
-                            command::search(SearchKind::First, event_sender.clone())
+                            command::search(
+                                SearchKind::First,
+                                search_options,
+                                config.incremental_search,
+                                event_sender.clone(),
+                            )
                         })
                         .paste(text, width)?
                 }
@@ -300,9 +752,43 @@ pub(crate) fn start(
                 Some(Event::SearchFirstMatch(index)) => screens
                     .get(index)
                     .and_then(|screen| screen.search_first_match()),
-                Some(Event::SearchFinished(index)) => screens
-                    .get(index)
-                    .and_then(|screen| screen.search_finished()),
+                Some(Event::SearchFinished(index)) => {
+                    let action = screens
+                        .get(index)
+                        .and_then(|screen| screen.search_finished());
+                    if screens.position(index).is_some() && screens.showing_search_summary() {
+                        screens.refresh_search_summary(&event_sender)?;
+                        Some(Action::Refresh)
+                    } else {
+                        action
+                    }
+                }
+                Some(Event::Watch(index)) => {
+                    // Wait for the previous run to finish before running the
+                    // command again.
+                    match screens.get(index) {
+                        Some(screen) if !screen.file.command_running() => {
+                            let size = term.get_screen_size()?;
+                            let size = (size.cols, size.rows);
+                            if let Err(e) = screens.rerun_command(index, true, size, &event_sender)
+                            {
+                                screens.current().error = Some(format!("{:#}", e));
+                            }
+                            Some(Action::Refresh)
+                        }
+                        _ => None,
+                    }
+                }
+                Some(Event::Saved(index, message)) => {
+                    if let Some(screen) = screens.get(index) {
+                        screen.error = Some(message);
+                    }
+                    if screens.is_current_index(index) {
+                        Some(Action::Refresh)
+                    } else {
+                        None
+                    }
+                }
                 _ => None,
             }
         };
@@ -342,25 +828,126 @@ pub(crate) fn start(
                         term.render(&screen.render(&caps)?)?;
                     }
                 }
-                Action::ShowHelp => {
-                    let overlay_index = screens.overlay_index + 1;
+                Action::ShowFileList => {
+                    let text = screens.file_list();
+                    let screen =
+                        screens.show_overlay("FILES", text, &event_sender, config.clone())?;
+                    screen.set_prompt(command::select_file());
+                    let size = term.get_screen_size()?;
+                    screen.resize(size.cols, size.rows);
+                    screen.refresh();
+                    term.render(&screen.render(&caps)?)?;
+                }
+                Action::SelectFile(number) => {
+                    screens.overlay = None;
+                    if number >= 1 && number <= screens.screens.len() {
+                        screens.current_index = number - 1;
+                    } else {
+                        screens.current().error = Some(format!("No file {}", number));
+                    }
+                    let screen = screens.current();
+                    let size = term.get_screen_size()?;
+                    screen.resize(size.cols, size.rows);
+                    screen.refresh();
+                    term.render(&screen.render(&caps)?)?;
+                }
+                Action::OpenFile(path) => {
+                    if let Err(e) = screens.open_file(&path, &event_sender, config.clone()) {
+                        screens.current().error = Some(format!("{:#}", e));
+                    }
                     let screen = screens.current();
-                    let mut screen = Screen::new(
-                        File::new_static(
-                            overlay_index,
-                            "HELP",
-                            help_text(screen.keymap())?.into_bytes(),
-                            event_sender.clone(),
-                        )?,
-                        config.clone(),
-                    )?;
                     let size = term.get_screen_size()?;
                     screen.resize(size.cols, size.rows);
                     screen.refresh();
                     term.render(&screen.render(&caps)?)?;
-                    screens.overlay = Some(screen);
-                    screens.overlay_index = overlay_index;
                 }
+                Action::RerunCommand => {
+                    let index = screens.current().file.index();
+                    let size = term.get_screen_size()?;
+                    let size = (size.cols, size.rows);
+                    if let Err(e) = screens.rerun_command(index, false, size, &event_sender) {
+                        screens.current().error = Some(format!("{:#}", e));
+                    }
+                    let screen = screens.current();
+                    let size = term.get_screen_size()?;
+                    screen.resize(size.cols, size.rows);
+                    screen.refresh();
+                    term.render(&screen.render(&caps)?)?;
+                }
+                Action::CloseFile => {
+                    if let Err(e) = screens.close_current() {
+                        screens.current().error = Some(e.to_string());
+                    }
+                    let screen = screens.current();
+                    let size = term.get_screen_size()?;
+                    screen.resize(size.cols, size.rows);
+                    screen.refresh();
+                    term.render(&screen.render(&caps)?)?;
+                }
+                Action::ShowHelp => {
+                    let text = help_text(screens.current().keymap())?;
+                    let screen =
+                        screens.show_overlay("HELP", text, &event_sender, config.clone())?;
+                    let size = term.get_screen_size()?;
+                    screen.resize(size.cols, size.rows);
+                    screen.refresh();
+                    term.render(&screen.render(&caps)?)?;
+                }
+                Action::ShowMarks => {
+                    let text = screens.current().marks_text();
+                    let screen =
+                        screens.show_overlay("MARKS", text, &event_sender, config.clone())?;
+                    let size = term.get_screen_size()?;
+                    screen.resize(size.cols, size.rows);
+                    screen.refresh();
+                    term.render(&screen.render(&caps)?)?;
+                }
+                Action::SearchAllFiles(pattern) => {
+                    let options = screens.current().search_options().get();
+                    match screens.search_all_files(&pattern, options, &event_sender) {
+                        Ok(()) => action = Some(Action::ShowSearchSummary),
+                        Err(e) => {
+                            screens.current().error = Some(e.to_string());
+                            action = Some(Action::Render);
+                        }
+                    }
+                }
+                Action::ShowSearchSummary => {
+                    let text = screens.search_summary();
+                    let screen =
+                        screens.show_overlay("SEARCH", text, &event_sender, config.clone())?;
+                    let size = term.get_screen_size()?;
+                    screen.resize(size.cols, size.rows);
+                    screen.refresh();
+                    term.render(&screen.render(&caps)?)?;
+                    screens.summary_index = Some(screens.overlay_index);
+                }
+                Action::NextMatchFile => match screens.match_file(true) {
+                    Some(index) => {
+                        screens.overlay = None;
+                        screens.current_index = index;
+                        let screen = screens.current();
+                        screen.move_match(MatchMotion::First);
+                        let size = term.get_screen_size()?;
+                        screen.resize(size.cols, size.rows);
+                        screen.refresh();
+                        term.render(&screen.render(&caps)?)?;
+                    }
+                    None => action = Some(Action::Render),
+                },
+                Action::PreviousMatchFile => match screens.match_file(false) {
+                    Some(index) => {
+                        screens.overlay = None;
+                        screens.current_index = index;
+                        let screen = screens.current();
+                        screen.move_match(MatchMotion::Last);
+                        let size = term.get_screen_size()?;
+                        screen.resize(size.cols, size.rows);
+                        screen.refresh();
+                        term.render(&screen.render(&caps)?)?;
+                    }
+                    None => action = Some(Action::Render),
+                },
                 Action::ClearOverlay => {
                     screens.overlay = None;
                     let screen = screens.current();
@@ -369,9 +956,92 @@ pub(crate) fn start(
                     screen.refresh();
                     term.render(&screen.render(&caps)?)?;
                 }
+                Action::PipeCommand(command, input) => {
+                    if let Err(e) =
+                        screens.add_command(&command, input, &event_sender, config.clone())
+                    {
+                        screens.current().error = Some(format!("{:#}", e));
+                    }
+                    let screen = screens.current();
+                    let size = term.get_screen_size()?;
+                    screen.resize(size.cols, size.rows);
+                    screen.refresh();
+                    term.render(&screen.render(&caps)?)?;
+                }
+                Action::OpenEditor(path, line) => {
+                    let status =
+                        with_terminal_restored(&mut *term, alternate_screen, false, || {
+                            editor::open(&path, line)
+                        })?;
+                    let screen = screens.current();
+                    match status {
+                        Ok(status) if status.success() => {
+                            if screen.file.path() == Some(&path) {
+                                screen.file.reload();
+                            } else {
+                                screen.error =
+                                    Some(format!("Edited copy is at {}", path.to_string_lossy()));
+                            }
+                        }
+                        Ok(status) => screen.error = Some(format!("Editor failed: {}", status)),
+                        Err(e) => screen.error = Some(format!("{:#}", e)),
+                    }
+                    let size = term.get_screen_size()?;
+                    screen.resize(size.cols, size.rows);
+                    screen.refresh();
+                    term.render(&screen.render(&caps)?)?;
+                }
+                Action::Suspend => {
+                    let result =
+                        with_terminal_restored(&mut *term, alternate_screen, false, suspend)?;
+                    let screen = screens.current();
+                    if let Err(e) = result {
+                        screen.error = Some(format!("{:#}", e));
+                    }
+                    let size = term.get_screen_size()?;
+                    screen.resize(size.cols, size.rows);
+                    screen.refresh();
+                    term.render(&screen.render(&caps)?)?;
+                }
+                Action::RunShellCommand(command) => {
+                    let status =
+                        with_terminal_restored(&mut *term, alternate_screen, true, || {
+                            util::attach_to_terminal(
+                                Command::new(util::shell()).arg("-c").arg(&command),
+                            )
+                            .and_then(|command| command.status())
+                        })?;
+                    let screen = screens.current();
+                    match status {
+                        Ok(status) if status.success() => {}
+                        Ok(status) => screen.error = Some(format!("Command failed: {}", status)),
+                        Err(e) => screen.error = Some(e.to_string()),
+                    }
+                    let size = term.get_screen_size()?;
+                    screen.resize(size.cols, size.rows);
+                    screen.refresh();
+                    term.render(&screen.render(&caps)?)?;
+                }
                 Action::Quit => {
+                    for screen in screens.screens.iter() {
+                        let _ = screen.save_session();
+                    }
+                    let files: Vec<File> = screens
+                        .screens
+                        .iter()
+                        .map(|screen| screen.file.clone())
+                        .collect();
                     let screen = screens.current();
                     overlay_height.store(screen.overlay_height(), Ordering::SeqCst);
+                    if finish_commands(
+                        &mut *term,
+                        &files,
+                        config.quit_policy,
+                        screen.overlay_height(),
+                        alternate_screen,
+                    )? {
+                        cleaned_up.store(true, Ordering::SeqCst);
+                    }
                     return Ok(());
                 }
             }
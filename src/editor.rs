@@ -0,0 +1,37 @@
+//! External editor.
+//!
+//! Files on disk can be opened in the user's editor, at the line at the top of
+//! the screen.
+use anyhow::{Context, Error};
+use std::env;
+use std::ffi::OsString;
+use std::path::Path;
+use std::process::{Command, ExitStatus};
+
+use crate::util;
+
+/// The editor to use if neither `$VISUAL` nor `$EDITOR` are set.
+const DEFAULT_EDITOR: &str = "vi";
+
+/// Open the file at `path` in the user's editor, at line `line`, and wait for
+/// the editor to exit.
+///
+/// The editor command is taken from `$VISUAL` or `$EDITOR`, and may include
+/// arguments separated by spaces.
+pub(crate) fn open(path: &Path, line: usize) -> Result<ExitStatus, Error> {
+    let editor = env::var_os("VISUAL")
+        .or_else(|| env::var_os("EDITOR"))
+        .filter(|editor| !editor.is_empty())
+        .unwrap_or_else(|| OsString::from(DEFAULT_EDITOR));
+    let editor = editor.to_string_lossy();
+    let mut words = editor.split_whitespace();
+    let program = words.next().unwrap_or(DEFAULT_EDITOR);
+    util::attach_to_terminal(
+        Command::new(program)
+            .args(words)
+            .arg(format!("+{}", line))
+            .arg(path),
+    )
+    .and_then(|command| command.status())
+    .with_context(|| program.to_string())
+}
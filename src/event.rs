@@ -33,6 +33,10 @@ pub(crate) enum Event {
     SearchFirstMatch(usize),
     /// Search has finished.
     SearchFinished(usize),
+    /// Saving a file has finished, with a message to show to the user.
+    Saved(usize, String),
+    /// It is time to run the command whose output is in a file again.
+    Watch(usize),
 }
 
 #[derive(Debug, Clone)]
@@ -1,23 +1,28 @@
 //! Files.
-use anyhow::{Context, Error, Result};
+use anyhow::{bail, Context, Error, Result};
 use memmap::Mmap;
 use notify::{DebouncedEvent, RecommendedWatcher, RecursiveMode, Watcher};
 use std::borrow::Cow;
 use std::cmp::{max, min};
-use std::ffi::OsStr;
+use std::ffi::{OsStr, OsString};
 use std::fs::File as StdFile;
-use std::io::{Read, Seek, SeekFrom};
+use std::io::{BufWriter, Cursor, Read, Seek, SeekFrom, Write};
+use std::ops::Range;
 use std::path::{Path, PathBuf};
-use std::process::{Command, Stdio};
+use std::process::{ChildStdin, Command, Stdio};
 use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::sync::mpsc;
-use std::sync::{Arc, Condvar, Mutex, RwLock};
+use std::sync::{Arc, Condvar, Mutex, RwLock, Weak};
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 use crate::buffer::Buffer;
 use crate::buffer_cache::BufferCache;
+use crate::decompress::{AutoDecoder, Compression, MAGIC_LENGTH};
 use crate::event::{Event, EventSender, UniqueInstance};
+use crate::preprocess::Preprocessed;
+use crate::pty::Pty;
+use crate::util;
 
 /// Buffer size to use when loading and parsing files.  This is also the block
 /// size when parsing memory mapped files or caching files read from disk.
@@ -41,7 +46,7 @@ enum FileData {
     },
 
     /// Data content has been memory mapped.
-    Mapped { mmap: Arc<Mmap> },
+    Mapped { path: PathBuf, mmap: Arc<Mmap> },
 
     /// File is empty.
     Empty,
@@ -88,6 +93,330 @@ struct FileMeta {
 
     /// Mutex used by waker.
     waker_mutex: Mutex<()>,
+
+    /// The process whose output or error is loaded into the file, if any.
+    process: Option<Arc<Process>>,
+
+    /// For files that combine a command's output and error, the lines that
+    /// came from the error.
+    error_lines: RwLock<Vec<usize>>,
+
+    /// For files that combine a command's output and error, the metadata of
+    /// the files for the output and error.  Loading them is resumed when
+    /// more lines of the combined file are needed.
+    sources: Vec<Arc<FileMeta>>,
+}
+
+/// A command whose output and error are loaded into files.
+#[derive(Clone)]
+struct CommandSpec {
+    /// The index of the file for the command's output.  Its error is loaded
+    /// into the file with the next index, and both are combined into the
+    /// file with the index after that.
+    index: usize,
+
+    /// The program to run.
+    command: OsString,
+
+    /// The arguments to pass to the program.
+    args: Vec<OsString>,
+
+    /// The command's standard input.
+    input: CommandInput,
+
+    /// The size of the pseudo-terminals to attach the command's output and
+    /// error to, if any.
+    pty_size: Option<(usize, usize)>,
+
+    /// The title of the command's output file.
+    title: String,
+}
+
+/// The standard input of a command.
+#[derive(Clone)]
+pub(crate) enum CommandInput {
+    /// The command's standard input is empty.
+    Empty,
+
+    /// The lines of a file are written to the command's standard input.
+    Lines(File, FileLines),
+
+    /// The command's standard input is kept open for input forwarded from
+    /// the pager.
+    Forwarded,
+}
+
+/// A selection of lines from a file.
+#[derive(Clone)]
+pub(crate) enum FileLines {
+    /// A range of lines.
+    Range(Range<usize>),
+
+    /// A list of line indexes.
+    List(Vec<usize>),
+}
+
+impl FileLines {
+    /// The number of lines selected.
+    pub(crate) fn len(&self) -> usize {
+        match self {
+            FileLines::Range(range) => range.len(),
+            FileLines::List(lines) => lines.len(),
+        }
+    }
+
+    /// Iterate over the indexes of the selected lines.
+    fn iter(&self) -> Box<dyn Iterator<Item = usize> + '_> {
+        match self {
+            FileLines::Range(range) => Box::new(range.clone()),
+            FileLines::List(lines) => Box::new(lines.iter().copied()),
+        }
+    }
+}
+
+/// A process running a command.
+struct Process {
+    /// The command the process is running.
+    spec: Arc<CommandSpec>,
+
+    /// The process ID.
+    pid: u32,
+
+    /// When the process was started.
+    started: Instant,
+
+    /// How long the process ran for, once it has exited.
+    runtime: Mutex<Option<Duration>>,
+
+    /// Notified when the process exits.
+    exited: Condvar,
+
+    /// The files that the process's output, error, and the two combined
+    /// are loaded into, while they are open.
+    files: Mutex<Vec<Weak<FileMeta>>>,
+
+    /// The process's standard input, while it is open for forwarded input.
+    stdin: Mutex<Option<ChildStdin>>,
+}
+
+impl Process {
+    /// Returns true once the process has exited.
+    fn exited(&self) -> bool {
+        self.runtime.lock().unwrap().is_some()
+    }
+
+    /// Wait for the process to exit.
+    fn wait(&self) {
+        let mut runtime = self.runtime.lock().unwrap();
+        while runtime.is_none() {
+            runtime = self.exited.wait(runtime).unwrap();
+        }
+    }
+
+    /// Load all of the process's output and error, even where it is not
+    /// needed, so that the process doesn't block writing them.
+    fn load_all(&self) {
+        for meta in self.files.lock().unwrap().iter().filter_map(Weak::upgrade) {
+            meta.set_needed_lines(usize::MAX);
+        }
+    }
+
+    /// Returns how long the process has been running for, or ran for if it
+    /// has exited.
+    fn runtime(&self) -> Duration {
+        self.runtime
+            .lock()
+            .unwrap()
+            .unwrap_or_else(|| self.started.elapsed())
+    }
+
+    /// Send a signal to the process, if it is still running.
+    ///
+    /// The process stays in the pager's process group, so that it can use
+    /// the terminal, for example to prompt for a password.  Processes it
+    /// started are left to the process to signal.
+    #[cfg(unix)]
+    fn signal(&self, signal: Signal) -> Result<(), Error> {
+        if self.exited() {
+            bail!("Command has already exited");
+        }
+        let signal = match signal {
+            Signal::Interrupt => libc::SIGINT,
+            Signal::Terminate => libc::SIGTERM,
+            Signal::Kill => libc::SIGKILL,
+        };
+        if unsafe { libc::kill(self.pid as libc::pid_t, signal) } != 0 {
+            return Err(std::io::Error::last_os_error().into());
+        }
+        Ok(())
+    }
+
+    /// Signals can't be sent on this platform.
+    #[cfg(not(unix))]
+    fn signal(&self, _signal: Signal) -> Result<(), Error> {
+        bail!("Signals are not supported on this platform")
+    }
+}
+
+/// A signal that can be sent to a command.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub(crate) enum Signal {
+    /// Interrupt the command, as if Ctrl-C was pressed.
+    Interrupt,
+
+    /// Ask the command to terminate.
+    Terminate,
+
+    /// Kill the command immediately.
+    Kill,
+}
+
+impl std::fmt::Display for Signal {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Signal::Interrupt => write!(f, "SIGINT"),
+            Signal::Terminate => write!(f, "SIGTERM"),
+            Signal::Kill => write!(f, "SIGKILL"),
+        }
+    }
+}
+
+/// One of the output streams of a command.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+enum Stream {
+    Output,
+    Error,
+}
+
+/// The output and error of a command, combined into a single file with the
+/// lines in the order they arrived.
+struct Combined {
+    /// Metadata for the combined file.
+    meta: Arc<FileMeta>,
+
+    /// The buffers that the combined file is stored in.
+    buffers: Arc<RwLock<Vec<Buffer>>>,
+
+    /// The state of combining the streams.
+    state: Mutex<CombinedState>,
+}
+
+/// One stream of a command, as it is appended to the combined file.
+///
+/// The stream is finished when this is dropped, so that the combined file
+/// finishes however loading the stream stops.
+struct CombinedStream {
+    combined: Arc<Combined>,
+    stream: Stream,
+
+    /// Sender for the event sent once both streams have been loaded.
+    event_sender: EventSender,
+}
+
+impl CombinedStream {
+    /// Add data that has been read from the stream.
+    fn append(&self, data: &[u8]) {
+        self.combined.append(self.stream, data);
+    }
+}
+
+impl Drop for CombinedStream {
+    fn drop(&mut self) {
+        if self.combined.finish(self.stream) {
+            let _ = self
+                .event_sender
+                .send(Event::Loaded(self.combined.meta.index));
+        }
+    }
+}
+
+struct CombinedState {
+    /// The partial line at the end of the data from each stream, which is
+    /// held back until it is complete.
+    partial: [Vec<u8>; 2],
+
+    /// The number of streams that haven't finished yet.
+    open_streams: usize,
+
+    /// The total size of the buffers.
+    total_buffer_size: usize,
+}
+
+impl Combined {
+    /// Create a new combined file for two streams.
+    fn new(meta: Arc<FileMeta>) -> Combined {
+        Combined {
+            meta,
+            buffers: Arc::new(RwLock::new(Vec::new())),
+            state: Mutex::new(CombinedState {
+                partial: [Vec::new(), Vec::new()],
+                open_streams: 2,
+                total_buffer_size: 0,
+            }),
+        }
+    }
+
+    /// Add data that has been read from `stream`.  Complete lines are
+    /// appended to the combined file as they arrive.
+    fn append(&self, stream: Stream, data: &[u8]) {
+        let mut state = self.state.lock().unwrap();
+        let partial = &mut state.partial[stream as usize];
+        partial.extend_from_slice(data);
+        if let Some(end) = partial.iter().rposition(|&byte| byte == b'\n') {
+            let lines: Vec<u8> = partial.drain(..=end).collect();
+            self.write(&mut state, stream, &lines);
+        }
+    }
+
+    /// Mark `stream` as finished.  Its final line is appended even if it is
+    /// incomplete, and once both streams have finished, so has the combined
+    /// file.  Returns true if the combined file has finished.
+    fn finish(&self, stream: Stream) -> bool {
+        let mut state = self.state.lock().unwrap();
+        let mut line = std::mem::take(&mut state.partial[stream as usize]);
+        if !line.is_empty() {
+            line.push(b'\n');
+            self.write(&mut state, stream, &line);
+        }
+        state.open_streams -= 1;
+        if state.open_streams == 0 {
+            self.meta.finished.store(true, Ordering::SeqCst);
+            return true;
+        }
+        false
+    }
+
+    /// Write complete lines from `stream` to the combined file.
+    fn write(&self, state: &mut CombinedState, stream: Stream, mut data: &[u8]) {
+        if self.meta.dropped.load(Ordering::SeqCst) {
+            return;
+        }
+        while !data.is_empty() {
+            let offset = self.meta.length.load(Ordering::SeqCst);
+            if offset == state.total_buffer_size {
+                let mut buffers = self.buffers.write().unwrap();
+                buffers.push(Buffer::new(BUFFER_SIZE));
+                state.total_buffer_size += BUFFER_SIZE;
+            }
+            let buffers = self.buffers.read().unwrap();
+            let mut write = buffers.last().unwrap().write();
+            let len = min(write.len(), data.len());
+            write[..len].copy_from_slice(&data[..len]);
+            let mut newlines = self.meta.newlines.write().unwrap();
+            let mut error_lines = self.meta.error_lines.write().unwrap();
+            for (i, byte) in data[..len].iter().enumerate() {
+                if *byte == b'\n' {
+                    if stream == Stream::Error {
+                        error_lines.push(newlines.len());
+                    }
+                    newlines.push(offset + i);
+                }
+            }
+            write.written(len);
+            self.meta.length.fetch_add(len, Ordering::SeqCst);
+            data = &data[len..];
+        }
+    }
 }
 
 /// Event triggered by changes to a file on disk.
@@ -124,8 +453,37 @@ impl FileMeta {
             needed_lines: AtomicUsize::new(DEFAULT_NEEDED_LINES),
             waker: Condvar::new(),
             waker_mutex: Mutex::new(()),
+            process: None,
+            error_lines: RwLock::new(Vec::new()),
+            sources: Vec::new(),
+        }
+    }
+
+    /// Check if loading has been paused.  Combined files are paused if
+    /// either of the files being combined is.
+    fn paused(&self) -> bool {
+        if self.finished.load(Ordering::SeqCst) {
+            false
+        } else if self.sources.is_empty() {
+            self.waker_mutex.try_lock().is_ok()
+        } else {
+            self.sources.iter().any(|source| source.paused())
         }
     }
+
+    /// Set how many lines are needed, resuming loading if it was paused.
+    /// For combined files, this is passed on to the files being combined.
+    fn set_needed_lines(&self, lines: usize) {
+        for source in self.sources.iter() {
+            source.set_needed_lines(lines);
+        }
+        // This can be simplified by `fetch_max` when it's stable.
+        if self.needed_lines.load(Ordering::SeqCst) >= lines {
+            return;
+        }
+        self.needed_lines.store(lines, Ordering::SeqCst);
+        self.waker.notify_all();
+    }
 }
 
 impl FileData {
@@ -134,14 +492,23 @@ impl FileData {
     /// A background thread is started to read from `input` and store the
     /// content in buffers.  Metadata about loading is written to `meta`.
     ///
+    /// If `combined` is provided, the data is also appended to that combined
+    /// file as the given stream of a command, in the order it arrives.
+    ///
     /// Returns `FileData` containing the buffers that the background thread
     /// is loading into.
     fn new_streamed(
         mut input: impl Read + Send + 'static,
         meta: Arc<FileMeta>,
+        combined: Option<(Arc<Combined>, Stream)>,
         event_sender: EventSender,
     ) -> Result<FileData, Error> {
         let buffers = Arc::new(RwLock::new(Vec::new()));
+        let combined = combined.map(|(combined, stream)| CombinedStream {
+            combined,
+            stream,
+            event_sender: event_sender.clone(),
+        });
         thread::spawn({
             let buffers = buffers.clone();
             move || -> Result<()> {
@@ -168,6 +535,9 @@ impl FileData {
                             if meta.dropped.load(Ordering::SeqCst) {
                                 return Ok(());
                             }
+                            if let Some(ref combined) = combined {
+                                combined.append(&write[..len]);
+                            }
                             // Some data has been read.  Parse its newlines.
                             let line_count = {
                                 let mut newlines = meta.newlines.write().unwrap();
@@ -194,8 +564,14 @@ impl FileData {
                         }
                         Err(ref e) if e.kind() == std::io::ErrorKind::Interrupted => {}
                         Err(e) => {
-                            let mut error = meta.error.write().unwrap();
-                            *error = Some(e.into());
+                            // Reading can't continue after an error, for
+                            // example from a truncated compressed file, as
+                            // it would fail again.  Stop with what has been
+                            // loaded so far.
+                            *meta.error.write().unwrap() = Some(e.into());
+                            meta.finished.store(true, Ordering::SeqCst);
+                            event_sender.send(Event::Loaded(meta.index))?;
+                            return Ok(());
                         }
                     }
                 }
@@ -403,6 +779,7 @@ impl FileData {
     ///
     /// Returns `FileData` containing the memory map.
     fn new_mapped(
+        path: &Path,
         file: StdFile,
         meta: Arc<FileMeta>,
         event_sender: EventSender,
@@ -437,7 +814,10 @@ impl FileData {
                 Ok(())
             }
         });
-        Ok(FileData::Mapped { mmap })
+        Ok(FileData::Mapped {
+            path: path.to_path_buf(),
+            mmap,
+        })
     }
 
     /// Create a new file from static data.
@@ -525,7 +905,7 @@ impl FileData {
                     })
                     .unwrap()
             }
-            FileData::Mapped { mmap } => call(Cow::Borrowed(&mmap[start..end])),
+            FileData::Mapped { mmap, .. } => call(Cow::Borrowed(&mmap[start..end])),
             FileData::Empty => call(Cow::Borrowed(&[])),
             FileData::Static { data } => call(Cow::Borrowed(&data[start..end])),
         }
@@ -559,7 +939,7 @@ impl File {
         event_sender: EventSender,
     ) -> Result<File, Error> {
         let meta = Arc::new(FileMeta::new(index, title.to_string()));
-        let data = FileData::new_streamed(stream, meta.clone(), event_sender)?;
+        let data = FileData::new_streamed(stream, meta.clone(), None, event_sender)?;
         Ok(File::new(data, meta))
     }
 
@@ -570,17 +950,90 @@ impl File {
     ) -> Result<File, Error> {
         let title = filename.to_string_lossy().into_owned();
         let meta = Arc::new(FileMeta::new(index, title.to_string()));
-        let mut file = StdFile::open(filename).context(title)?;
+        let mut file = StdFile::open(filename).context(title.clone())?;
         // Determine whether this file is a real file, or some kind of pipe, by
         // attempting to do a no-op seek.  If it fails, we won't be able to seek
         // around and load parts of the file at will, so treat it as a stream.
-        let data = match file.seek(SeekFrom::Current(0)) {
-            Ok(_) => FileData::new_file(filename, meta.clone(), event_sender)?,
-            Err(_) => FileData::new_streamed(file, meta.clone(), event_sender)?,
+        let seekable = file.seek(SeekFrom::Current(0)).is_ok();
+        if !seekable {
+            // Reading from a stream may block, so check whether it is
+            // compressed in the thread that loads it.
+            let input = AutoDecoder::new(
+                file,
+                Box::new({
+                    let meta = meta.clone();
+                    move |compression| {
+                        let mut info = meta.info.write().unwrap();
+                        info.push(compression.name().to_string());
+                    }
+                }),
+            );
+            let data = FileData::new_streamed(input, meta.clone(), None, event_sender)?;
+            return Ok(File::new(data, meta));
+        }
+        // Check the start of the file for the magic bytes of compressed data.
+        let mut magic = Vec::with_capacity(MAGIC_LENGTH);
+        (&mut file)
+            .take(MAGIC_LENGTH as u64)
+            .read_to_end(&mut magic)
+            .context(title)?;
+        let data = match Compression::detect(&magic) {
+            Some(compression) => {
+                meta.info
+                    .write()
+                    .unwrap()
+                    .push(compression.name().to_string());
+                let input = compression.decoder(Cursor::new(magic).chain(file))?;
+                FileData::new_streamed(input, meta.clone(), None, event_sender)?
+            }
+            None => FileData::new_file(filename, meta.clone(), event_sender)?,
         };
         Ok(File::new(data, meta))
     }
 
+    /// Load a file through a preprocessor command.
+    ///
+    /// The preprocessor is run by the shell, with `%s` replaced by the file
+    /// name.  If it produces output, that output is loaded instead of the
+    /// file.  Otherwise the file is loaded as a stream.  The preprocessor's
+    /// output is waited for in the thread that loads the file.
+    pub(crate) fn new_preprocessed(
+        index: usize,
+        filename: &OsStr,
+        preprocessor: &str,
+        event_sender: EventSender,
+    ) -> Result<File, Error> {
+        let title = filename.to_string_lossy().into_owned();
+        let meta = Arc::new(FileMeta::new(index, title.clone()));
+        let file = StdFile::open(filename).context(title)?;
+        // The file name is passed to the shell as a positional parameter so
+        // that it doesn't need to be quoted.
+        let command = preprocessor.replace("%s", "\"$1\"");
+        let process = Command::new(util::shell())
+            .arg("-c")
+            .arg(&command)
+            .arg("sp")
+            .arg(filename)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .with_context(|| preprocessor.to_string())?;
+        let file = AutoDecoder::new(
+            file,
+            Box::new({
+                let meta = meta.clone();
+                move |compression| {
+                    let mut info = meta.info.write().unwrap();
+                    info.push(compression.name().to_string());
+                }
+            }),
+        );
+        let input = Preprocessed::new(process, file);
+        let data = FileData::new_streamed(input, meta.clone(), None, event_sender)?;
+        Ok(File::new(data, meta))
+    }
+
     /// Load a file by memory mapping it if possible.
     #[allow(unused)]
     pub(crate) fn new_mapped(
@@ -595,53 +1048,279 @@ impl File {
         // attempting to do a no-op seek.  If it fails, assume we can't mmap
         // it.
         let data = match file.seek(SeekFrom::Current(0)) {
-            Ok(_) => FileData::new_mapped(file, meta.clone(), event_sender)?,
-            Err(_) => FileData::new_streamed(file, meta.clone(), event_sender)?,
+            Ok(_) => FileData::new_mapped(filename.as_ref(), file, meta.clone(), event_sender)?,
+            Err(_) => FileData::new_streamed(file, meta.clone(), None, event_sender)?,
         };
         Ok(File::new(data, meta))
     }
 
-    /// Load the output and error of a command
+    /// Load the output and error of a command, with `input` as its standard
+    /// input.  If `pty_size` is provided,
+    /// the command's output and error are pseudo-terminals of that size.
     pub(crate) fn new_command<I, S>(
         index: usize,
         command: &OsStr,
         args: I,
+        input: CommandInput,
+        pty_size: Option<(usize, usize)>,
         title: &str,
         event_sender: EventSender,
-    ) -> Result<(File, File), Error>
+    ) -> Result<(File, File, File), Error>
     where
         I: IntoIterator<Item = S>,
         S: AsRef<OsStr>,
     {
-        let title_err = format!("STDERR for {}", title);
-        let mut process = Command::new(command)
-            .args(args)
-            .stdin(Stdio::null())
-            .stdout(Stdio::piped())
-            .stderr(Stdio::piped())
+        let spec = CommandSpec {
+            index,
+            command: command.to_os_string(),
+            args: args
+                .into_iter()
+                .map(|arg| arg.as_ref().to_os_string())
+                .collect(),
+            input,
+            pty_size,
+            title: title.to_string(),
+        };
+        File::run_command(Arc::new(spec), event_sender)
+    }
+
+    /// Run a command, loading its output and error, and the two combined.
+    fn run_command(
+        spec: Arc<CommandSpec>,
+        event_sender: EventSender,
+    ) -> Result<(File, File, File), Error> {
+        let ptys = match spec.pty_size {
+            Some((cols, rows)) => Some((Pty::open(cols, rows)?, Pty::open(cols, rows)?)),
+            None => None,
+        };
+        let (stdout, stderr) = match ptys {
+            Some((ref out_pty, ref err_pty)) => (out_pty.stdio()?, err_pty.stdio()?),
+            None => (Stdio::piped(), Stdio::piped()),
+        };
+        let mut command = Command::new(&spec.command);
+        command
+            .args(&spec.args)
+            .stdin(match spec.input {
+                CommandInput::Empty => Stdio::null(),
+                CommandInput::Lines(..) | CommandInput::Forwarded => Stdio::piped(),
+            })
+            .stdout(stdout)
+            .stderr(stderr);
+        let mut child = command
             .spawn()
-            .context(command.to_string_lossy().into_owned())?;
-        let out = process.stdout.take().unwrap();
-        let err = process.stderr.take().unwrap();
-        let out_file = File::new_streamed(index, out, &title, event_sender.clone())?;
-        let err_file = File::new_streamed(index + 1, err, &title_err, event_sender.clone())?;
+            .context(spec.command.to_string_lossy().into_owned())?;
+        drop(command);
+        let mut stdin = child.stdin.take();
+        if let CommandInput::Lines(..) = spec.input {
+            if let Some(stdin) = stdin.take() {
+                // Write the input from another thread so that the command can
+                // produce output while it is being written.  The command may
+                // exit without reading all of its input, so stop at the first
+                // write error.
+                let spec = spec.clone();
+                thread::spawn(move || -> std::io::Result<()> {
+                    if let CommandInput::Lines(ref file, ref lines) = spec.input {
+                        let mut stdin = BufWriter::new(stdin);
+                        for line in lines.iter() {
+                            file.with_line(line, |data| stdin.write_all(&data))
+                                .unwrap_or(Ok(()))?;
+                        }
+                        stdin.flush()?;
+                    }
+                    Ok(())
+                });
+            }
+        }
+        let (out, err): (Box<dyn Read + Send>, Box<dyn Read + Send>) = match ptys {
+            Some((out_pty, err_pty)) => (
+                Box::new(out_pty.into_reader()),
+                Box::new(err_pty.into_reader()),
+            ),
+            None => (
+                Box::new(child.stdout.take().unwrap()),
+                Box::new(child.stderr.take().unwrap()),
+            ),
+        };
+        let process = Arc::new(Process {
+            spec: spec.clone(),
+            pid: child.id(),
+            started: Instant::now(),
+            runtime: Mutex::new(None),
+            exited: Condvar::new(),
+            files: Mutex::new(Vec::new()),
+            stdin: Mutex::new(stdin),
+        });
+        let mut out_meta = FileMeta::new(spec.index, spec.title.clone());
+        out_meta.process = Some(process.clone());
+        let mut err_meta = FileMeta::new(spec.index + 1, format!("STDERR for {}", spec.title));
+        err_meta.process = Some(process.clone());
+        let (out_meta, err_meta) = (Arc::new(out_meta), Arc::new(err_meta));
+        let mut combined_meta =
+            FileMeta::new(spec.index + 2, format!("STDOUT+STDERR for {}", spec.title));
+        combined_meta.process = Some(process.clone());
+        combined_meta.sources = vec![out_meta.clone(), err_meta.clone()];
+        let combined_meta = Arc::new(combined_meta);
+        *process.files.lock().unwrap() = vec![
+            Arc::downgrade(&out_meta),
+            Arc::downgrade(&err_meta),
+            Arc::downgrade(&combined_meta),
+        ];
+        let combined = Arc::new(Combined::new(combined_meta.clone()));
+        let out_data = FileData::new_streamed(
+            out,
+            out_meta.clone(),
+            Some((combined.clone(), Stream::Output)),
+            event_sender.clone(),
+        )?;
+        let err_data = FileData::new_streamed(
+            err,
+            err_meta.clone(),
+            Some((combined.clone(), Stream::Error)),
+            event_sender.clone(),
+        )?;
+        let combined_data = FileData::Streamed {
+            buffers: combined.buffers.clone(),
+        };
+        let out_file = File::new(out_data, out_meta);
+        let err_file = File::new(err_data, err_meta);
+        let combined_file = File::new(combined_data, combined_meta);
         thread::spawn({
             let out_file = out_file.clone();
             move || -> Result<()> {
-                if let Ok(rc) = process.wait() {
+                let status = child.wait();
+                *process.runtime.lock().unwrap() = Some(process.started.elapsed());
+                process.exited.notify_all();
+                if let Ok(rc) = status {
                     if !rc.success() {
                         let mut info = out_file.meta.info.write().unwrap();
                         match rc.code() {
                             Some(code) => info.push(format!("rc: {}", code)),
                             None => info.push("killed!".to_string()),
                         }
-                        event_sender.send(Event::RefreshOverlay)?;
                     }
                 }
+                event_sender.send(Event::RefreshOverlay)?;
                 Ok(())
             }
         });
-        Ok((out_file, err_file))
+        Ok((out_file, err_file, combined_file))
+    }
+
+    /// Run the command whose output or error is loaded into this file
+    /// again, returning new files for its output, error and the two combined.
+    /// If the command is still running, it is terminated first.
+    ///
+    /// Commands that were run on pseudo-terminals are given new ones of
+    /// `size`, the current size of the screen.
+    pub(crate) fn rerun(
+        &self,
+        size: (usize, usize),
+        event_sender: EventSender,
+    ) -> Result<(File, File, File), Error> {
+        match self.meta.process {
+            Some(ref process) => {
+                let _ = process.signal(Signal::Terminate);
+                let spec = match process.spec.pty_size {
+                    Some(pty_size) if pty_size != size => Arc::new(CommandSpec {
+                        pty_size: Some(size),
+                        ..CommandSpec::clone(&process.spec)
+                    }),
+                    _ => process.spec.clone(),
+                };
+                File::run_command(spec, event_sender)
+            }
+            None => bail!("{} is not the output of a command", self.meta.title),
+        }
+    }
+
+    /// True if the command whose output or error is loaded into this file is
+    /// still running.
+    pub(crate) fn command_running(&self) -> bool {
+        match self.meta.process {
+            Some(ref process) => !process.exited(),
+            None => false,
+        }
+    }
+
+    /// True if input can be forwarded to the command whose output or error
+    /// is loaded into this file.
+    pub(crate) fn accepts_command_input(&self) -> bool {
+        match self.meta.process {
+            Some(ref process) => {
+                matches!(process.spec.input, CommandInput::Forwarded)
+                    && !process.exited()
+                    && process.stdin.lock().unwrap().is_some()
+            }
+            None => false,
+        }
+    }
+
+    /// Write `data` to the standard input of the command whose output or
+    /// error is loaded into this file.
+    pub(crate) fn write_command_input(&self, data: &[u8]) -> Result<(), Error> {
+        let process = match self.meta.process {
+            Some(ref process) => process,
+            None => bail!("{} is not the output of a command", self.meta.title),
+        };
+        if process.exited() {
+            bail!("Command has already exited");
+        }
+        if !matches!(process.spec.input, CommandInput::Forwarded) {
+            bail!("Input forwarding is not enabled for this command");
+        }
+        let mut stdin = process.stdin.lock().unwrap();
+        match *stdin {
+            Some(ref mut stdin) => {
+                stdin.write_all(data)?;
+                stdin.flush()?;
+                Ok(())
+            }
+            None => bail!("Command input is closed"),
+        }
+    }
+
+    /// Close the standard input of the command whose output or error is
+    /// loaded into this file, so that it reaches the end of its input.
+    pub(crate) fn close_command_input(&self) -> Result<(), Error> {
+        match self.meta.process {
+            Some(ref process) => match process.stdin.lock().unwrap().take() {
+                Some(_) => Ok(()),
+                None => bail!("Command input is already closed"),
+            },
+            None => bail!("{} is not the output of a command", self.meta.title),
+        }
+    }
+
+    /// Send a signal to the command whose output or error is loaded into
+    /// this file.
+    pub(crate) fn signal_command(&self, signal: Signal) -> Result<(), Error> {
+        match self.meta.process {
+            Some(ref process) => process.signal(signal),
+            None => bail!("{} is not the output of a command", self.meta.title),
+        }
+    }
+
+    /// Wait for the command whose output or error is loaded into this file
+    /// to exit.
+    ///
+    /// Loading pauses once enough lines have been read, after which the
+    /// command would block writing its output, so all of the command's
+    /// output and error are loaded while waiting, including for files that
+    /// are no longer displayed.
+    pub(crate) fn wait_command(&self) {
+        if let Some(ref process) = self.meta.process {
+            process.load_all();
+            process.wait();
+        }
+    }
+
+    /// The title of the command whose output or error is loaded into this
+    /// file.
+    pub(crate) fn command_title(&self) -> Option<&str> {
+        self.meta
+            .process
+            .as_ref()
+            .map(|process| process.spec.title.as_str())
     }
 
     /// Load a file from static data.
@@ -666,10 +1345,46 @@ impl File {
         &self.meta.title
     }
 
-    /// The file's info.
+    /// The file's info.  For the output of commands, this includes the
+    /// process ID and how long the command has been running for.
     pub(crate) fn info(&self) -> String {
         let info = self.meta.info.read().unwrap();
-        info.join(" ")
+        match self.meta.process {
+            Some(ref process) => {
+                let mut process_info = vec![
+                    format!("pid: {}", process.pid),
+                    format!("time: {}", format_duration(process.runtime())),
+                ];
+                process_info.extend(info.iter().cloned());
+                process_info.join(" ")
+            }
+            None => info.join(" "),
+        }
+    }
+
+    /// True if the file's data is read from a file on disk, either through a
+    /// memory map or the buffer cache, rather than from a stream.
+    pub(crate) fn is_on_disk(&self) -> bool {
+        match self.data {
+            FileData::File { .. } | FileData::Mapped { .. } => true,
+            FileData::Streamed { .. } | FileData::Empty | FileData::Static { .. } => false,
+        }
+    }
+
+    /// The path of the file on disk, if its data is read from one.
+    pub(crate) fn path(&self) -> Option<&Path> {
+        match self.data {
+            FileData::File { ref path, .. } | FileData::Mapped { ref path, .. } => Some(path),
+            FileData::Streamed { .. } | FileData::Empty | FileData::Static { .. } => None,
+        }
+    }
+
+    /// Reload the file from disk.  Only files that are read through the
+    /// buffer cache can be reloaded.
+    pub(crate) fn reload(&self) {
+        if let FileData::File { ref events, .. } = self.data {
+            let _ = events.send(FileEvent::Reload);
+        }
     }
 
     /// True once the file is loaded and all newlines have been parsed.
@@ -692,6 +1407,24 @@ impl File {
         )
     }
 
+    /// True if line `index` came from a command's error, for files that
+    /// combine a command's output and error.
+    pub(crate) fn is_error_line(&self, index: usize) -> bool {
+        let error_lines = self.meta.error_lines.read().unwrap();
+        error_lines.binary_search(&index).is_ok()
+    }
+
+    /// Returns the index of the line that contains the byte at `offset`.
+    /// Offsets past the end of the loaded data are in the last line.
+    pub(crate) fn line_at_offset(&self, offset: usize) -> usize {
+        let newlines = self.meta.newlines.read().unwrap();
+        line_at_offset(
+            newlines.as_slice(),
+            self.meta.length.load(Ordering::SeqCst),
+            offset,
+        )
+    }
+
     /// Runs the `call` function, passing it the contents of line `index`.
     /// Tries to avoid copying the data if possible, however the borrowed
     /// line only lasts as long as the function call.
@@ -719,23 +1452,34 @@ impl File {
         Some(self.data.with_slice(start, end, call))
     }
 
+    /// Runs the `call` function, passing it the contents of line `index`.
+    /// Lines read through the buffer cache are copied out of it first, so
+    /// that it isn't locked while `call` runs, and other threads can read
+    /// the file at the same time.
+    pub(crate) fn with_line_unlocked<T, F>(&self, index: usize, mut call: F) -> Option<T>
+    where
+        F: FnMut(Cow<'_, [u8]>) -> T,
+    {
+        match self.data {
+            FileData::File { .. } => self
+                .with_line(index, |data| data.into_owned())
+                .map(|data| call(Cow::Owned(data))),
+            _ => self.with_line(index, call),
+        }
+    }
+
     /// Set how many lines are needed.
     ///
     /// If `self.lines()` exceeds that number, pause loading until
     /// `set_needed_lines` is called with a larger number.
     /// This is only effective for "streamed" input.
     pub(crate) fn set_needed_lines(&self, lines: usize) {
-        // This can be simplified by `fetch_max` when it's stable.
-        if self.meta.needed_lines.load(Ordering::SeqCst) >= lines {
-            return;
-        }
-        self.meta.needed_lines.store(lines, Ordering::SeqCst);
-        self.meta.waker.notify_all();
+        self.meta.set_needed_lines(lines);
     }
 
     /// Check if the loading thread has been paused.
     pub(crate) fn paused(&self) -> bool {
-        !self.loaded() && self.meta.waker_mutex.try_lock().is_ok()
+        self.meta.paused()
     }
 }
 
@@ -748,6 +1492,23 @@ impl Drop for FileGuard {
     }
 }
 
+/// Format a duration in whole seconds, with minutes and hours if needed.
+fn format_duration(duration: Duration) -> String {
+    let seconds = duration.as_secs();
+    if seconds < 60 {
+        format!("{}s", seconds)
+    } else if seconds < 3600 {
+        format!("{}m{:02}s", seconds / 60, seconds % 60)
+    } else {
+        format!(
+            "{}h{:02}m{:02}s",
+            seconds / 3600,
+            seconds / 60 % 60,
+            seconds % 60
+        )
+    }
+}
+
 fn line_count(newlines: &[usize], length: usize) -> usize {
     let mut lines = newlines.len();
     let after_last_newline_offset = if lines == 0 {
@@ -760,3 +1521,84 @@ fn line_count(newlines: &[usize], length: usize) -> usize {
     }
     lines
 }
+
+/// Returns the index of the line that contains the byte at `offset`, given
+/// the offsets of the newlines and the total length of the data.
+fn line_at_offset(newlines: &[usize], length: usize, offset: usize) -> usize {
+    let last_line = line_count(newlines, length).saturating_sub(1);
+    min(
+        newlines.partition_point(|&newline| newline < offset),
+        last_line,
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Returns the lines that have been loaded into `file`.
+    fn loaded_lines(file: &File) -> Vec<String> {
+        (0..file.lines())
+            .map(|index| {
+                file.with_line(index, |data| String::from_utf8_lossy(&data).into_owned())
+                    .unwrap()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_combined() {
+        let meta = Arc::new(FileMeta::new(0, String::from("combined")));
+        let combined = Combined::new(meta.clone());
+        let file = File::new(
+            FileData::Streamed {
+                buffers: combined.buffers.clone(),
+            },
+            meta,
+        );
+
+        // Partial lines are held back until they are complete.
+        combined.append(Stream::Output, b"out 1\nout ");
+        combined.append(Stream::Error, b"err 1\n");
+        assert_eq!(loaded_lines(&file), ["out 1\n", "err 1\n"]);
+        combined.append(Stream::Error, b"err 2");
+        combined.append(Stream::Output, b"2\nout 3\n");
+        assert_eq!(
+            loaded_lines(&file),
+            ["out 1\n", "err 1\n", "out 2\n", "out 3\n"]
+        );
+
+        // The combined file finishes once both streams have, and an
+        // unterminated final line is added when its stream finishes.
+        assert!(!combined.finish(Stream::Output));
+        assert!(!file.loaded());
+        assert!(combined.finish(Stream::Error));
+        assert!(file.loaded());
+        assert_eq!(
+            loaded_lines(&file),
+            ["out 1\n", "err 1\n", "out 2\n", "out 3\n", "err 2\n"]
+        );
+        let error_lines: Vec<bool> = (0..5).map(|index| file.is_error_line(index)).collect();
+        assert_eq!(error_lines, [false, true, false, false, true]);
+    }
+
+    #[test]
+    fn test_line_at_offset() {
+        // "one\ntwo\n\nfour"
+        let newlines = [3, 7, 8];
+        let length = 13;
+        assert_eq!(line_at_offset(&newlines, length, 0), 0);
+        assert_eq!(line_at_offset(&newlines, length, 3), 0);
+        assert_eq!(line_at_offset(&newlines, length, 4), 1);
+        assert_eq!(line_at_offset(&newlines, length, 7), 1);
+        assert_eq!(line_at_offset(&newlines, length, 8), 2);
+        assert_eq!(line_at_offset(&newlines, length, 9), 3);
+        assert_eq!(line_at_offset(&newlines, length, 12), 3);
+        assert_eq!(line_at_offset(&newlines, length, 1000), 3);
+
+        // Offsets past a final newline are in the last line.
+        assert_eq!(line_at_offset(&newlines, 9, 9), 2);
+        assert_eq!(line_at_offset(&[], 0, 0), 0);
+        assert_eq!(line_at_offset(&[], 5, 3), 0);
+    }
+}
@@ -0,0 +1,183 @@
+//! Filtering.
+//!
+//! A filter restricts the lines of a file that are shown on the screen.  It
+//! is made up of a stack of layers, each of which either includes only the
+//! lines that match a pattern, or excludes the lines that match a pattern.
+//! A line is shown if it passes every layer.
+//!
+//! Matching is performed by a background `Search` for each layer, and the
+//! filter collects the lines that pass as the searches progress.
+use anyhow::Error;
+
+use crate::event::EventSender;
+use crate::file::File;
+use crate::search::{Search, SearchKind, SearchOptions};
+
+/// A single layer of a filter.
+struct FilterLayer {
+    /// The search that finds the lines matching this layer's pattern.
+    search: Search,
+
+    /// Whether matching lines are excluded rather than included.
+    exclude: bool,
+}
+
+impl FilterLayer {
+    /// Returns true if the line passes this layer.
+    fn passes(&self, line_index: usize) -> bool {
+        self.search.line_matches(line_index) != self.exclude
+    }
+}
+
+/// A filter over the lines of a file.
+pub(crate) struct Filter {
+    /// The layers of the filter, in the order they were added.
+    layers: Vec<FilterLayer>,
+
+    /// The indexes of the file lines that pass the filter, in order.
+    lines: Vec<usize>,
+
+    /// The number of file lines that have been checked against the filter.
+    checked_lines: usize,
+}
+
+impl Filter {
+    /// Create a new filter for `file` with a single layer.  If `exclude` is
+    /// true, lines that match `pattern` are hidden, otherwise only lines that
+    /// match `pattern` are shown.  The pattern is interpreted according to
+    /// `options`.
+    pub(crate) fn new(
+        file: &File,
+        pattern: &str,
+        options: SearchOptions,
+        exclude: bool,
+        event_sender: EventSender,
+    ) -> Result<Filter, Error> {
+        let mut filter = Filter {
+            layers: Vec::new(),
+            lines: Vec::new(),
+            checked_lines: 0,
+        };
+        filter.push(file, pattern, options, exclude, event_sender)?;
+        Ok(filter)
+    }
+
+    /// Create a filter with the same layers as this one for another file.
+    pub(crate) fn for_file(&self, file: &File, event_sender: EventSender) -> Result<Filter, Error> {
+        let mut filter = Filter {
+            layers: Vec::new(),
+            lines: Vec::new(),
+            checked_lines: 0,
+        };
+        for layer in self.layers.iter() {
+            filter.push(
+                file,
+                layer.search.pattern(),
+                layer.search.options(),
+                layer.exclude,
+                event_sender.clone(),
+            )?;
+        }
+        Ok(filter)
+    }
+
+    /// Add a new layer to the filter.
+    pub(crate) fn push(
+        &mut self,
+        file: &File,
+        pattern: &str,
+        options: SearchOptions,
+        exclude: bool,
+        event_sender: EventSender,
+    ) -> Result<(), Error> {
+        let search = Search::new(file, pattern, options, SearchKind::Filter, event_sender)?;
+        self.layers.push(FilterLayer { search, exclude });
+        self.reset();
+        Ok(())
+    }
+
+    /// Remove the most recently added layer from the filter.
+    ///
+    /// Returns true if there are any layers remaining.
+    pub(crate) fn pop(&mut self) -> bool {
+        self.layers.pop();
+        self.reset();
+        !self.layers.is_empty()
+    }
+
+    /// Forget which lines pass the filter so that they are collected again
+    /// from the layers' searches.
+    fn reset(&mut self) {
+        self.lines.clear();
+        self.checked_lines = 0;
+    }
+
+    /// Returns the number of lines that all layers have searched.
+    fn searched_lines(&self) -> usize {
+        self.layers
+            .iter()
+            .map(|layer| layer.search.searched_lines())
+            .min()
+            .unwrap_or(0)
+    }
+
+    /// Collect any lines that the searches have checked since the last
+    /// update.
+    ///
+    /// Returns true if the filter has changed.
+    pub(crate) fn update(&mut self) -> bool {
+        let searched_lines = self.searched_lines();
+        if searched_lines <= self.checked_lines {
+            return false;
+        }
+        for line_index in self.checked_lines..searched_lines {
+            if self.layers.iter().all(|layer| layer.passes(line_index)) {
+                self.lines.push(line_index);
+            }
+        }
+        self.checked_lines = searched_lines;
+        true
+    }
+
+    /// Returns true once the whole file has been checked against the filter.
+    pub(crate) fn finished(&self) -> bool {
+        self.layers.iter().all(|layer| layer.search.finished())
+            && self.checked_lines == self.searched_lines()
+    }
+
+    /// Returns the number of file lines that have been checked.
+    pub(crate) fn checked_lines(&self) -> usize {
+        self.checked_lines
+    }
+
+    /// Returns the number of lines that pass the filter.
+    pub(crate) fn lines(&self) -> usize {
+        self.lines.len()
+    }
+
+    /// Returns the file line index of the `index`th line that passes the
+    /// filter.
+    pub(crate) fn file_line(&self, index: usize) -> Option<usize> {
+        self.lines.get(index).cloned()
+    }
+
+    /// Returns the index of the first line that passes the filter at or after
+    /// file line `file_line`.
+    pub(crate) fn filtered_line(&self, file_line: usize) -> usize {
+        match self.lines.binary_search(&file_line) {
+            Ok(index) | Err(index) => index,
+        }
+    }
+
+    /// Returns a description of the filter for display in the ruler.
+    pub(crate) fn description(&self) -> String {
+        let mut description = String::from("filter:");
+        for layer in self.layers.iter() {
+            description.push(' ');
+            description.push(if layer.exclude { '-' } else { '+' });
+            description.push_str(layer.search.pattern());
+        }
+        description.push_str(&format!(" ({} lines)", self.lines.len()));
+        description
+    }
+}
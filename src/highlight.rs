@@ -0,0 +1,83 @@
+//! Highlighting.
+//!
+//! Highlights are patterns that are shown in a distinct color wherever they
+//! appear in the file, independently of the current search.
+use anyhow::{anyhow, Error};
+use regex::bytes::Regex;
+use termwiz::color::{AnsiColor, ColorAttribute};
+
+use crate::search::SearchOptions;
+
+/// Colors that are assigned to new highlights in turn.
+const HIGHLIGHT_COLORS: [AnsiColor; 6] = [
+    AnsiColor::Red,
+    AnsiColor::Yellow,
+    AnsiColor::Blue,
+    AnsiColor::Lime,
+    AnsiColor::Fuschia,
+    AnsiColor::Aqua,
+];
+
+/// A pattern that is highlighted wherever it appears.
+#[derive(Debug, Clone)]
+pub(crate) struct Highlight {
+    /// The regex that matches the pattern.
+    regex: Regex,
+
+    /// The color to highlight matches in.
+    color: ColorAttribute,
+}
+
+impl Highlight {
+    /// Create a new highlight for a pattern.
+    pub(crate) fn new(
+        pattern: &str,
+        options: SearchOptions,
+        color: impl Into<ColorAttribute>,
+    ) -> Result<Highlight, Error> {
+        Ok(Highlight {
+            regex: options.regex(pattern)?,
+            color: color.into(),
+        })
+    }
+
+    /// Returns the color to use for the `index`th highlight when no color is
+    /// specified.
+    pub(crate) fn default_color(index: usize) -> AnsiColor {
+        HIGHLIGHT_COLORS[index % HIGHLIGHT_COLORS.len()]
+    }
+
+    /// Returns the regex that matches the pattern.
+    pub(crate) fn regex(&self) -> &Regex {
+        &self.regex
+    }
+
+    /// Returns the color to highlight matches in.
+    pub(crate) fn color(&self) -> ColorAttribute {
+        self.color
+    }
+}
+
+/// Parse the name of a color.
+pub(crate) fn parse_color(name: &str) -> Result<AnsiColor, Error> {
+    let color = match name.to_ascii_lowercase().as_ref() {
+        "black" => AnsiColor::Black,
+        "red" => AnsiColor::Red,
+        "green" => AnsiColor::Lime,
+        "yellow" => AnsiColor::Yellow,
+        "blue" => AnsiColor::Blue,
+        "magenta" => AnsiColor::Fuschia,
+        "cyan" => AnsiColor::Aqua,
+        "white" => AnsiColor::White,
+        "dark-red" => AnsiColor::Maroon,
+        "dark-green" => AnsiColor::Green,
+        "dark-yellow" => AnsiColor::Olive,
+        "dark-blue" => AnsiColor::Navy,
+        "dark-magenta" => AnsiColor::Purple,
+        "dark-cyan" => AnsiColor::Teal,
+        "grey" | "gray" => AnsiColor::Grey,
+        "silver" => AnsiColor::Silver,
+        _ => return Err(anyhow!("unknown color: {}", name)),
+    };
+    Ok(color)
+}
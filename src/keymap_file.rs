@@ -190,3 +190,61 @@ impl KeymapFile {
         self.0.iter()
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn parse(data: &str) -> Result<Vec<((Modifiers, KeyCode), Binding)>> {
+        Ok(KeymapFile::parse(data)?
+            .iter()
+            .into_iter()
+            .map(|(key, binding_config)| (*key, binding_config.binding.clone()))
+            .collect())
+    }
+
+    #[test]
+    fn test_quoted_keys() {
+        let keymap = parse(concat!(
+            "'x' => Quit;\n",
+            "'\\'' => PromptGoToMark;\n",
+            "CTRL '\\\\', ' ' => Refresh;\n",
+        ))
+        .unwrap();
+        assert_eq!(
+            keymap,
+            vec![
+                ((Modifiers::NONE, KeyCode::Char('x')), Binding::Quit),
+                (
+                    (Modifiers::NONE, KeyCode::Char('\'')),
+                    Binding::PromptGoToMark
+                ),
+                ((Modifiers::CTRL, KeyCode::Char('\\')), Binding::Refresh),
+                ((Modifiers::NONE, KeyCode::Char(' ')), Binding::Refresh),
+            ]
+        );
+        assert!(parse("'xy' => Quit;\n").is_err());
+        assert!(parse("'x => Quit;\n").is_err());
+    }
+
+    #[test]
+    fn test_quoted_params() {
+        let keymap = parse(concat!(
+            "'m' => SetMark('a');\n",
+            "'g' => GoToMark('\\'');\n",
+        ))
+        .unwrap();
+        assert_eq!(
+            keymap,
+            vec![
+                ((Modifiers::NONE, KeyCode::Char('m')), Binding::SetMark('a')),
+                (
+                    (Modifiers::NONE, KeyCode::Char('g')),
+                    Binding::GoToMark('\'')
+                ),
+            ]
+        );
+        assert!(parse("'m' => SetMark(1);\n").is_err());
+        assert!(parse("'m' => SetMark('ab');\n").is_err());
+    }
+}
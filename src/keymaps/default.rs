@@ -20,16 +20,42 @@ keymap! {
     SHIFT RightArrow => ScrollRightScreenFraction(4);
     '[', SHIFT Tab => PreviousFile;
     ']', Tab => NextFile;
+    'l' => ShowFileList;
     'h', F 1 => Help;
+    's' => PromptSaveFile;
+    '|' => PromptPipe;
+    'v' => OpenEditor;
+    CTRL 'Z' => Suspend;
+    '!' => PromptShellCommand;
+    'e' => PromptOpenFile;
+    'X' => CloseFile;
+    'R' => RerunCommand;
+    ALT 'i' => InterruptCommand;
+    ALT 't' => TerminateCommand;
+    ALT 'k' => KillCommand;
+    'i' => PromptCommandInput;
     '#' => ToggleLineNumbers;
     '\\' => ToggleLineWrapping;
+    'H' => PromptHighlight;
+    ALT 'h' => RemoveHighlight;
+    ALT 'H' => ClearHighlights;
     ':', '%' => PromptGoToLine;
+    CTRL 'O', ALT LeftArrow => JumpBack;
+    ALT RightArrow => JumpForward;
+    'm' => PromptSetMark;
+    '\'' => PromptGoToMark;
+    'M' => ShowMarks;
     '/' => PromptSearchForwards;
     '?' => PromptSearchBackwards;
+    ALT '/' => PromptSearchAllFiles;
     ',' => PreviousMatch;
     '.' => NextMatch;
     'p', ('N') => PreviousMatchLine;
     'n' => NextMatchLine;
     '(' => FirstMatch;
     ')' => LastMatch;
+    '&' => PromptFilter;
+    '*' => PromptFilterOut;
+    '=' => PopFilter;
+    ALT '=' => ClearFilter;
 }
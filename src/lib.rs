@@ -10,6 +10,8 @@ pub use anyhow::Result;
 use std::ffi::OsStr;
 use std::io::Read;
 use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
 use termwiz::caps::ColorLevel;
 use termwiz::caps::{Capabilities, ProbeHints};
 use termwiz::terminal::{SystemTerminal, Terminal};
@@ -21,11 +23,15 @@ mod buffer;
 mod buffer_cache;
 mod command;
 pub mod config;
+mod decompress;
 mod direct;
 mod display;
+mod editor;
 mod event;
 mod file;
+mod filter;
 mod help;
+mod highlight;
 #[cfg(feature = "keymap-file")]
 mod keymap_file;
 #[macro_use]
@@ -35,19 +41,25 @@ mod line;
 mod line_cache;
 mod line_drawing;
 mod overstrike;
+mod preprocess;
 mod progress;
 mod prompt;
 mod prompt_history;
+mod pty;
 mod refresh;
 mod ruler;
+mod save;
 mod screen;
 mod search;
+mod session;
 mod util;
 
 use bindings::Keymap;
-use config::{Config, InterfaceMode, KeymapConfig, WrappingMode};
-use event::EventStream;
-use file::File;
+use config::{
+    Config, InterfaceMode, KeymapConfig, QuitPolicy, SearchCase, SearchMode, WrappingMode,
+};
+use event::{Event, EventStream};
+use file::{CommandInput, File};
 use progress::Progress;
 
 /// The main pager state.
@@ -70,6 +82,9 @@ pub struct Pager {
     /// Progress indicators to display.
     progress: Option<Progress>,
 
+    /// How often to run subprocesses again, if they are being watched.
+    watch_interval: Option<Duration>,
+
     /// Configuration.
     config: Config,
 }
@@ -131,6 +146,7 @@ impl Pager {
         let files = Vec::new();
         let error_files = VecMap::new();
         let progress = None;
+        let watch_interval = None;
         let config = Config::from_config_file().with_env();
 
         Ok(Self {
@@ -140,6 +156,7 @@ impl Pager {
             files,
             error_files,
             progress,
+            watch_interval,
             config,
         })
     }
@@ -177,7 +194,12 @@ impl Pager {
     pub fn add_output_file(&mut self, filename: &OsStr) -> Result<&mut Self> {
         let index = self.files.len();
         let event_sender = self.events.sender();
-        let file = File::new_file(index, filename, event_sender)?;
+        let file = match self.config.preprocessor {
+            Some(ref preprocessor) => {
+                File::new_preprocessed(index, filename, preprocessor, event_sender)?
+            }
+            None => File::new_file(index, filename, event_sender)?,
+        };
         self.files.push(file);
         Ok(self)
     }
@@ -195,10 +217,32 @@ impl Pager {
     {
         let index = self.files.len();
         let event_sender = self.events.sender();
-        let (out_file, err_file) = File::new_command(index, command, args, title, event_sender)?;
+        let pty_size = if self.config.pty {
+            let size = self.term.get_screen_size()?;
+            Some((size.cols, size.rows))
+        } else {
+            None
+        };
+        let input = if self.config.forward_input {
+            CommandInput::Forwarded
+        } else {
+            CommandInput::Empty
+        };
+        let (out_file, err_file, combined_file) =
+            File::new_command(index, command, args, input, pty_size, title, event_sender)?;
         self.error_files.insert(index, err_file.clone());
         self.files.push(out_file);
         self.files.push(err_file);
+        self.files.push(combined_file);
+        if let Some(interval) = self.watch_interval {
+            let event_sender = self.events.sender();
+            thread::spawn(move || loop {
+                thread::sleep(interval);
+                if event_sender.send(Event::Watch(index)).is_err() {
+                    break;
+                }
+            });
+        }
         Ok(self)
     }
 
@@ -233,6 +277,61 @@ impl Pager {
         self
     }
 
+    /// Set default search mode. See [`SearchMode`] for details.
+    pub fn set_search_mode(&mut self, value: impl Into<SearchMode>) -> &mut Self {
+        self.config.search_mode = value.into();
+        self
+    }
+
+    /// Set default search case sensitivity. See [`SearchCase`] for details.
+    pub fn set_search_case(&mut self, value: impl Into<SearchCase>) -> &mut Self {
+        self.config.search_case = value.into();
+        self
+    }
+
+    /// Set whether to search as the search term is typed.
+    pub fn set_incremental_search(&mut self, value: bool) -> &mut Self {
+        self.config.incremental_search = value;
+        self
+    }
+
+    /// Set the command used to preprocess files.  `%s` is replaced by the
+    /// name of the file.
+    pub fn set_preprocessor(&mut self, preprocessor: Option<String>) -> &mut Self {
+        self.config.preprocessor = preprocessor;
+        self
+    }
+
+    /// Set whether subprocesses are run with their output and error streams
+    /// attached to pseudo-terminals, so that they behave as if they were
+    /// writing to the terminal.
+    pub fn set_pty(&mut self, value: bool) -> &mut Self {
+        self.config.pty = value;
+        self
+    }
+
+    /// Set whether subprocesses are run with their standard input kept open,
+    /// so that lines typed into the pager can be forwarded to them.
+    pub fn set_forward_input(&mut self, value: bool) -> &mut Self {
+        self.config.forward_input = value;
+        self
+    }
+
+    /// Set how often subprocesses are run again, like `watch`.  Lines that
+    /// change between runs are marked.  This applies to subprocesses added
+    /// after it is set.
+    pub fn set_watch_interval(&mut self, interval: Option<Duration>) -> &mut Self {
+        self.watch_interval = interval;
+        self
+    }
+
+    /// Set what happens to commands that are still running when the pager
+    /// quits.
+    pub fn set_quit_policy(&mut self, value: QuitPolicy) -> &mut Self {
+        self.config.quit_policy = value;
+        self
+    }
+
     /// Set keymap name.
     pub fn set_keymap_name(&mut self, keymap: impl Into<String>) -> &mut Self {
         self.config.keymap = KeymapConfig::Name(keymap.into());
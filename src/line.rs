@@ -3,11 +3,11 @@ use lru_cache::LruCache;
 use regex::bytes::{NoExpand, Regex};
 use smallvec::SmallVec;
 use std::borrow::Cow;
-use std::cmp::Ordering;
+use std::cmp::{max, Ordering};
 use std::str;
 use std::sync::{Arc, Mutex};
 use termwiz::cell::{CellAttributes, Intensity};
-use termwiz::color::{AnsiColor, ColorAttribute};
+use termwiz::color::{AnsiColor, ColorAttribute, ColorSpec};
 use termwiz::escape::csi::{Sgr, CSI};
 use termwiz::escape::esc::{Esc, EscCode};
 use termwiz::escape::osc::OperatingSystemCommand;
@@ -19,6 +19,7 @@ use unicode_segmentation::UnicodeSegmentation;
 use unicode_width::UnicodeWidthStr;
 
 use crate::config::WrappingMode;
+use crate::highlight::Highlight;
 use crate::line_drawing;
 use crate::overstrike;
 use crate::search::{trim_trailing_newline, ESCAPE_SEQUENCE};
@@ -54,6 +55,8 @@ enum OutputStyle {
     Match,
     /// The currently selected search match.
     CurrentMatch,
+    /// A highlighted pattern.
+    Highlight(ColorAttribute),
 }
 
 /// Tracker of current attributes state.
@@ -148,6 +151,12 @@ impl AttributeState {
                     .set_background(AnsiColor::Teal)
                     .set_intensity(Intensity::Normal)
                     .clone(),
+                OutputStyle::Highlight(color) => self
+                    .attrs
+                    .clone()
+                    .set_foreground(color)
+                    .set_intensity(Intensity::Bold)
+                    .clone(),
             };
             self.style = style;
             self.changed = false;
@@ -165,6 +174,8 @@ enum Span {
     Text(String),
     /// Text that matches the current search, and the search match index.
     Match(String, usize),
+    /// Text that matches a highlighted pattern, and the highlight color.
+    Highlight(String, ColorAttribute),
     /// A control character.
     Control(u8),
     /// An invalid UTF-8 byte.
@@ -187,6 +198,53 @@ enum Span {
     LF,
 }
 
+/// The matches for the current search within a line.
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum SearchMatches<'a> {
+    /// The matches of a regex within the line, marked with their index
+    /// within the line.
+    Regex(&'a Regex),
+    /// Ranges of the line covered by matches, and the index each range is
+    /// marked with.
+    Ranges(&'a [(usize, usize, usize)]),
+}
+
+/// How a range of text within a line is marked.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Marking {
+    /// A match for the current search, and the search match index.
+    Match(usize),
+    /// A match for a highlighted pattern, and the highlight color.
+    Highlight(ColorAttribute),
+}
+
+/// Add a marking for the range `start..end` to a list of markings sorted by
+/// their start.  Parts of the range that are already marked keep their
+/// existing marking.
+fn add_marking(
+    markings: &mut Vec<(usize, usize, Marking)>,
+    start: usize,
+    end: usize,
+    marking: Marking,
+) {
+    let mut pieces = Vec::new();
+    let mut position = start;
+    for &(mark_start, mark_end, _) in markings.iter() {
+        if mark_end <= position || mark_start >= end {
+            continue;
+        }
+        if mark_start > position {
+            pieces.push((position, mark_start, marking));
+        }
+        position = max(position, mark_end);
+    }
+    if position < end {
+        pieces.push((position, end, marking));
+    }
+    markings.extend(pieces);
+    markings.sort_by_key(|&(mark_start, _, _)| mark_start);
+}
+
 /// Produce `Change`s to output some text in the given style at the given
 /// position, truncated to the start and end columns.
 ///
@@ -307,6 +365,22 @@ impl Span {
                     position,
                 )?;
             }
+            Span::Highlight(ref t, color) => {
+                let text = if attr_state.line_drawing {
+                    Cow::Owned(line_drawing::convert_line_drawing(t.as_str()))
+                } else {
+                    Cow::Borrowed(t.as_str())
+                };
+                position = write_truncated(
+                    changes,
+                    attr_state,
+                    OutputStyle::Highlight(color),
+                    text.as_ref(),
+                    start,
+                    end,
+                    position,
+                )?;
+            }
             Span::TAB => {
                 let tabchars = 8 - position % 8;
                 position = write_truncated(
@@ -360,7 +434,7 @@ impl Span {
         words: bool,
     ) -> (usize, usize) {
         match self {
-            Span::Text(text) | Span::Match(text, _) => {
+            Span::Text(text) | Span::Match(text, _) | Span::Highlight(text, _) => {
                 let mut start = start;
                 let mut position = position;
                 if words {
@@ -452,11 +526,19 @@ impl Span {
 }
 
 /// Parse data into an array of Spans.
-fn parse_spans(data: &[u8], match_index: Option<usize>) -> Vec<Span> {
+fn parse_spans(data: &[u8], marking: Option<Marking>) -> Vec<Span> {
     let mut spans = Vec::new();
     let mut input = &data[..];
 
-    fn parse_unicode_span(data: &str, spans: &mut Vec<Span>, match_index: Option<usize>) {
+    fn text_span(text: &str, marking: Option<Marking>) -> Span {
+        match marking {
+            Some(Marking::Match(match_index)) => Span::Match(text.to_string(), match_index),
+            Some(Marking::Highlight(color)) => Span::Highlight(text.to_string(), color),
+            None => Span::Text(text.to_string()),
+        }
+    }
+
+    fn parse_unicode_span(data: &str, spans: &mut Vec<Span>, marking: Option<Marking>) {
         let mut text_start = None;
         let mut skip_to = None;
         for (index, grapheme) in data.grapheme_indices(true) {
@@ -548,11 +630,7 @@ fn parse_spans(data: &[u8], match_index: Option<usize>) -> Vec<Span> {
 
             if let Some(span) = span {
                 if let Some(start) = text_start {
-                    if let Some(match_index) = match_index {
-                        spans.push(Span::Match(data[start..index].to_string(), match_index));
-                    } else {
-                        spans.push(Span::Text(data[start..index].to_string()));
-                    }
+                    spans.push(text_span(&data[start..index], marking));
                     text_start = None;
                 }
                 spans.push(span);
@@ -561,29 +639,21 @@ fn parse_spans(data: &[u8], match_index: Option<usize>) -> Vec<Span> {
             }
         }
         if let Some(start) = text_start {
-            if let Some(match_index) = match_index {
-                spans.push(Span::Match(data[start..].to_string(), match_index));
-            } else {
-                spans.push(Span::Text(data[start..].to_string()));
-            }
+            spans.push(text_span(&data[start..], marking));
         }
     }
 
     loop {
         match str::from_utf8(input) {
             Ok(valid) => {
-                parse_unicode_span(valid, &mut spans, match_index);
+                parse_unicode_span(valid, &mut spans, marking);
                 break;
             }
             Err(error) => {
                 let (valid, after_valid) = input.split_at(error.valid_up_to());
                 if !valid.is_empty() {
                     unsafe {
-                        parse_unicode_span(
-                            str::from_utf8_unchecked(valid),
-                            &mut spans,
-                            match_index,
-                        );
+                        parse_unicode_span(str::from_utf8_unchecked(valid), &mut spans, marking);
                     }
                 }
                 if let Some(len) = error.error_len() {
@@ -612,7 +682,17 @@ impl Line {
         Line { spans, wraps }
     }
 
-    pub(crate) fn new_search(_index: usize, data: impl AsRef<[u8]>, regex: &Regex) -> Line {
+    /// Create a line with matches for the current search and any highlights
+    /// marked.
+    pub(crate) fn new_marked(
+        index: usize,
+        data: impl AsRef<[u8]>,
+        matches: Option<SearchMatches<'_>>,
+        highlights: &[Highlight],
+    ) -> Line {
+        if matches.is_none() && highlights.is_empty() {
+            return Line::new(index, data);
+        }
         let data = data.as_ref();
         let data = overstrike::convert_overstrike(&data[..]);
         let len = trim_trailing_newline(&data[..]);
@@ -642,20 +722,47 @@ impl Line {
         } else {
             (Cow::Borrowed(&data[..len]), None)
         };
-        for (match_index, match_range) in regex.find_iter(&data_without_escapes[..]).enumerate() {
-            let (match_start, match_end) = if let Some(ref convert) = convert_offset {
-                (convert(match_range.start()), convert(match_range.end()))
+        let mut markings = Vec::new();
+        match matches {
+            Some(SearchMatches::Regex(regex)) => {
+                for (match_index, match_range) in
+                    regex.find_iter(&data_without_escapes[..]).enumerate()
+                {
+                    markings.push((
+                        match_range.start(),
+                        match_range.end(),
+                        Marking::Match(match_index),
+                    ));
+                }
+            }
+            Some(SearchMatches::Ranges(ranges)) => {
+                for &(range_start, range_end, match_index) in ranges.iter() {
+                    markings.push((range_start, range_end, Marking::Match(match_index)));
+                }
+            }
+            None => {}
+        }
+        for highlight in highlights.iter() {
+            for match_range in highlight.regex().find_iter(&data_without_escapes[..]) {
+                add_marking(
+                    &mut markings,
+                    match_range.start(),
+                    match_range.end(),
+                    Marking::Highlight(highlight.color()),
+                );
+            }
+        }
+        for (mark_start, mark_end, marking) in markings.into_iter() {
+            let (mark_start, mark_end) = if let Some(ref convert) = convert_offset {
+                (convert(mark_start), convert(mark_end))
             } else {
-                (match_range.start(), match_range.end())
+                (mark_start, mark_end)
             };
-            if start < match_start {
-                spans.append(&mut parse_spans(&data[start..match_start], None));
+            if start < mark_start {
+                spans.append(&mut parse_spans(&data[start..mark_start], None));
             }
-            spans.append(&mut parse_spans(
-                &data[match_start..match_end],
-                Some(match_index),
-            ));
-            start = match_end;
+            spans.append(&mut parse_spans(&data[mark_start..mark_end], Some(marking)));
+            start = mark_end;
         }
         if start < data.len() {
             spans.append(&mut parse_spans(&data[start..], None));
@@ -665,6 +772,37 @@ impl Line {
         Line { spans, wraps }
     }
 
+    /// Tint the text of the line with `color`.  Any colors set by escape
+    /// sequences in the line take precedence, and the tint is applied again
+    /// after sequences that reset the colors.
+    pub(crate) fn tinted(self, color: AnsiColor) -> Line {
+        let tint = Sgr::Foreground(color.into());
+        let mut spans = Vec::with_capacity(self.spans.len() + 1);
+        let mut sgr_sequence = SmallVec::new();
+        sgr_sequence.push(tint.clone());
+        spans.push(Span::SgrSequence(sgr_sequence));
+        for span in self.spans.into_vec() {
+            match span {
+                Span::SgrSequence(sequence) => {
+                    let mut tinted = SmallVec::with_capacity(sequence.len());
+                    for sgr in sequence {
+                        let reset = matches!(sgr, Sgr::Reset | Sgr::Foreground(ColorSpec::Default));
+                        tinted.push(sgr);
+                        if reset {
+                            tinted.push(tint.clone());
+                        }
+                    }
+                    spans.push(Span::SgrSequence(tinted));
+                }
+                span => spans.push(span),
+            }
+        }
+        Line {
+            spans: spans.into_boxed_slice(),
+            wraps: self.wraps,
+        }
+    }
+
     /// Produce the `Change`s needed to render a slice of the line on a terminal.
     pub(crate) fn render(
         &self,
@@ -801,7 +939,6 @@ impl Line {
 mod test {
     use super::Span::*;
     use super::*;
-    use termwiz::color::ColorSpec;
 
     #[test]
     fn test_parse_spans() {
@@ -925,6 +1062,78 @@ mod test {
         );
     }
 
+    #[test]
+    fn test_tinted() {
+        let red = || Sgr::Foreground(AnsiColor::Red.into());
+        let line = Line::new(0, b"a\x1B[34mb\x1B[0mc\x1B[1;39md\n").tinted(AnsiColor::Red);
+        assert_eq!(
+            &line.spans[..],
+            &[
+                SgrSequence(SmallVec::from(&[red()][..])),
+                Text("a".to_string()),
+                SgrSequence(SmallVec::from(
+                    &[Sgr::Foreground(ColorSpec::PaletteIndex(4))][..]
+                )),
+                Text("b".to_string()),
+                SgrSequence(SmallVec::from(&[Sgr::Reset, red()][..])),
+                Text("c".to_string()),
+                SgrSequence(SmallVec::from(
+                    &[
+                        Sgr::Intensity(Intensity::Bold),
+                        Sgr::Foreground(ColorSpec::Default),
+                        red()
+                    ][..]
+                )),
+                Text("d".to_string()),
+                LF,
+            ][..]
+        );
+    }
+
+    #[test]
+    fn test_new_marked() {
+        let regex = Regex::new("error").unwrap();
+        let options = crate::search::SearchOptions::default();
+        let highlights = vec![
+            crate::highlight::Highlight::new("an error", options, AnsiColor::Red).unwrap(),
+            crate::highlight::Highlight::new("id=[0-9]+", options, AnsiColor::Blue).unwrap(),
+        ];
+        let line = Line::new_marked(
+            0,
+            b"an error id=42\n",
+            Some(SearchMatches::Regex(&regex)),
+            &highlights,
+        );
+        assert_eq!(
+            &line.spans[..],
+            &[
+                Highlight("an ".to_string(), AnsiColor::Red.into()),
+                Match("error".to_string(), 0),
+                Text(" ".to_string()),
+                Highlight("id=42".to_string(), AnsiColor::Blue.into()),
+                LF,
+            ][..]
+        );
+    }
+
+    #[test]
+    fn test_new_marked_ranges() {
+        let line = Line::new_marked(
+            0,
+            b"at src/main.rs:10\n",
+            Some(SearchMatches::Ranges(&[(0, 14, 3)])),
+            &[],
+        );
+        assert_eq!(
+            &line.spans[..],
+            &[
+                Match("at src/main.rs".to_string(), 3),
+                Text(":10".to_string()),
+                LF,
+            ][..]
+        );
+    }
+
     #[test]
     fn test_wrap() {
         let data = concat!(
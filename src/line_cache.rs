@@ -2,11 +2,12 @@
 //!
 //! An LRU-cache for lines.
 use lru_cache::LruCache;
-use regex::bytes::Regex;
 use std::borrow::Cow;
+use termwiz::color::AnsiColor;
 
 use crate::file::File;
-use crate::line::Line;
+use crate::highlight::Highlight;
+use crate::line::{Line, SearchMatches};
 
 /// An LRU-cache for Lines.
 pub(crate) struct LineCache(LruCache<usize, Line>);
@@ -18,22 +19,25 @@ impl LineCache {
     }
 
     /// Get a line out of the line cache, or create it if it is not
-    /// in the cache.
+    /// in the cache.  New lines have the search `matches` and any
+    /// `highlights` marked.
     pub(crate) fn get_or_create<'a>(
         &'a mut self,
         file: &File,
         line_index: usize,
-        regex: Option<&Regex>,
+        matches: Option<SearchMatches<'_>>,
+        highlights: &[Highlight],
     ) -> Option<Cow<'a, Line>> {
         let cache = &mut self.0;
         if cache.contains_key(&line_index) {
             Some(Cow::Borrowed(cache.get_mut(&line_index).unwrap()))
         } else {
             let line = file.with_line(line_index, |line| {
-                if let Some(ref regex) = regex {
-                    Line::new_search(line_index, line, regex)
+                let line = Line::new_marked(line_index, line, matches, highlights);
+                if file.is_error_line(line_index) {
+                    line.tinted(AnsiColor::Red)
                 } else {
-                    Line::new(line_index, line)
+                    line
                 }
             });
             if let Some(line) = line {
@@ -0,0 +1,96 @@
+//! Preprocessing.
+//!
+//! Files can be read through a preprocessor command, whose output is shown
+//! instead of the file.  If the command produces no output, the file itself
+//! is read instead.
+use std::fs::File as StdFile;
+use std::io::Read;
+use std::process::{Child, ChildStdout};
+use std::thread;
+
+use crate::decompress::AutoDecoder;
+
+/// The state of a `Preprocessed` reader.
+enum PreprocessorState {
+    /// The output of the preprocessor is being read.  The file is kept until
+    /// the preprocessor produces some output, in case it has none.
+    Running {
+        process: Child,
+        output: ChildStdout,
+        file: Option<AutoDecoder<StdFile>>,
+    },
+
+    /// The file is being read, as the preprocessor had no output.
+    Original(AutoDecoder<StdFile>),
+
+    /// The output of the preprocessor has been read.
+    Finished,
+}
+
+/// A reader that reads the output of a preprocessor, or the file it was run
+/// on if the preprocessor produces no output.
+///
+/// Waiting for the preprocessor's first output blocks, so this allows the
+/// choice to be made on the thread that loads the stream.
+pub(crate) struct Preprocessed {
+    state: PreprocessorState,
+}
+
+impl Preprocessed {
+    /// Read from the preprocessor `process`, which must have been started
+    /// with its output piped, falling back to `file`.
+    pub(crate) fn new(mut process: Child, file: AutoDecoder<StdFile>) -> Preprocessed {
+        let output = process.stdout.take().expect("preprocessor output is piped");
+        Preprocessed {
+            state: PreprocessorState::Running {
+                process,
+                output,
+                file: Some(file),
+            },
+        }
+    }
+}
+
+impl Read for Preprocessed {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        loop {
+            match self.state {
+                PreprocessorState::Running {
+                    ref mut process,
+                    ref mut output,
+                    ref mut file,
+                } => {
+                    let len = output.read(buf)?;
+                    if len != 0 {
+                        *file = None;
+                        return Ok(len);
+                    }
+                    let _ = process.wait();
+                    let file = file.take();
+                    self.state = match file {
+                        Some(file) => PreprocessorState::Original(file),
+                        None => PreprocessorState::Finished,
+                    };
+                }
+                PreprocessorState::Original(ref mut file) => return file.read(buf),
+                PreprocessorState::Finished => return Ok(0),
+            }
+        }
+    }
+}
+
+impl Drop for Preprocessed {
+    fn drop(&mut self) {
+        if let PreprocessorState::Running {
+            mut process,
+            output,
+            ..
+        } = std::mem::replace(&mut self.state, PreprocessorState::Finished)
+        {
+            // Closing the output lets the preprocessor exit, so that it can
+            // be waited for.
+            drop(output);
+            thread::spawn(move || process.wait());
+        }
+    }
+}
@@ -1,7 +1,9 @@
 //! Prompts for input.
 use anyhow::Error;
+use std::borrow::Cow;
 use std::char;
 use std::fmt::Write;
+use std::rc::Rc;
 use termwiz::cell::{AttributeChange, CellAttributes};
 use termwiz::color::{AnsiColor, ColorAttribute};
 use termwiz::input::KeyEvent;
@@ -15,6 +17,18 @@ use crate::screen::Screen;
 use crate::util;
 
 type PromptRunFn = dyn FnMut(&mut Screen, &str) -> Result<Option<Action>, Error>;
+type PromptChangeFn = dyn Fn(&mut Screen, &str) -> Result<Option<Action>, Error>;
+type PromptCancelFn = dyn FnMut(&mut Screen) -> Result<Option<Action>, Error>;
+type PromptCompleteFn = dyn Fn(&str) -> Option<String>;
+
+/// Options that the user can change while a prompt is open.
+pub(crate) trait PromptOptions {
+    /// Handle a key press.  Returns true if the key changed the options.
+    fn dispatch_key(&self, key: &KeyEvent) -> bool;
+
+    /// Describe the current options for display in the prompt.
+    fn describe(&self) -> String;
+}
 
 /// A prompt for input from the user.
 pub(crate) struct Prompt {
@@ -26,6 +40,29 @@ pub(crate) struct Prompt {
 
     /// The closure to run when the user presses Return.  Will only be called once.
     run: Option<Box<PromptRunFn>>,
+
+    /// Options that can be changed while the prompt is open.
+    options: Option<Rc<dyn PromptOptions>>,
+
+    /// The closure to run whenever the value or options change.
+    change: Option<Rc<PromptChangeFn>>,
+
+    /// The closure to run when the user presses Escape.  Will only be called once.
+    cancel: Option<Box<PromptCancelFn>>,
+
+    /// Whether the prompt finishes as soon as a single character is typed.
+    single_key: bool,
+
+    /// Whether values entered in the prompt are saved to the history file.
+    save_history: bool,
+
+    /// The closure to run when the user presses Ctrl-D with an empty value.
+    /// Will only be called once.
+    end_of_input: Option<Box<PromptCancelFn>>,
+
+    /// The closure to run when the user presses Tab.  Returns the completed
+    /// value, if the value can be completed.
+    complete: Option<Box<PromptCompleteFn>>,
 }
 
 pub(crate) struct PromptState {
@@ -163,6 +200,13 @@ impl PromptState {
         Ok(())
     }
 
+    /// Replace the value, moving to the end.
+    fn set_value(&mut self, value: &str) -> Option<Action> {
+        self.value = value.chars().collect();
+        self.position = self.value.len();
+        Some(Action::RefreshPrompt)
+    }
+
     /// Insert a character at the current position.
     fn insert_char(&mut self, c: char, width: usize) -> Option<Action> {
         self.value.insert(self.position, c);
@@ -334,6 +378,98 @@ impl Prompt {
             prompt: prompt.to_string(),
             history: PromptHistory::open(ident),
             run: Some(run),
+            options: None,
+            change: None,
+            cancel: None,
+            single_key: false,
+            save_history: true,
+            end_of_input: None,
+            complete: None,
+        }
+    }
+
+    /// Finish the prompt as soon as a single character is typed, without
+    /// waiting for the user to press Return.
+    pub(crate) fn with_single_key(mut self) -> Prompt {
+        self.single_key = true;
+        self
+    }
+
+    /// Don't save values entered in the prompt to the history file, as they
+    /// may be sensitive.
+    pub(crate) fn without_saved_history(mut self) -> Prompt {
+        self.save_history = false;
+        self
+    }
+
+    /// Run a closure if the user presses Ctrl-D when the value is empty.
+    pub(crate) fn with_end_of_input(mut self, end_of_input: Box<PromptCancelFn>) -> Prompt {
+        self.end_of_input = Some(end_of_input);
+        self
+    }
+
+    /// Complete the value when the user presses Tab.
+    pub(crate) fn with_completion(mut self, complete: Box<PromptCompleteFn>) -> Prompt {
+        self.complete = Some(complete);
+        self
+    }
+
+    /// Returns the action that runs the prompt's closure with the current
+    /// value.
+    fn finish(&mut self) -> Action {
+        if !self.single_key && self.save_history {
+            let _ = self.history.save();
+        }
+        let mut run = self.run.take();
+        let value: String = self.state().value[..].iter().collect();
+        Action::Run(Box::new(move |screen: &mut Screen| {
+            screen.clear_prompt();
+            if let Some(ref mut run) = run {
+                run(screen, &value)
+            } else {
+                Ok(Some(Action::Render))
+            }
+        }))
+    }
+
+    /// Run a closure whenever the value or options change.
+    pub(crate) fn with_change(mut self, change: Rc<PromptChangeFn>) -> Prompt {
+        self.change = Some(change);
+        self
+    }
+
+    /// Run a closure if the user cancels the prompt.
+    pub(crate) fn with_cancel(mut self, cancel: Box<PromptCancelFn>) -> Prompt {
+        self.cancel = Some(cancel);
+        self
+    }
+
+    /// Returns the action to take when the value or options have changed.
+    fn changed(&self) -> Option<Action> {
+        let change = self.change.clone()?;
+        let value: String = self.state().value[..].iter().collect();
+        Some(Action::Run(Box::new(move |screen: &mut Screen| {
+            screen.refresh_prompt();
+            change(screen, &value)
+        })))
+    }
+
+    /// Allow the user to change options while the prompt is open.
+    pub(crate) fn with_options(mut self, options: Rc<dyn PromptOptions>) -> Prompt {
+        self.options = Some(options);
+        self
+    }
+
+    /// Returns the label to display before the value, including the current
+    /// options.
+    fn label(&self) -> Cow<'_, str> {
+        match self.options {
+            Some(ref options) => Cow::Owned(format!(
+                "{} [{}]:",
+                self.prompt.trim_end_matches(':'),
+                options.describe()
+            )),
+            None => Cow::Borrowed(&self.prompt),
         }
     }
 
@@ -347,7 +483,7 @@ impl Prompt {
 
     /// Returns the column for the cursor.
     pub(crate) fn cursor_position(&self) -> usize {
-        self.prompt.width() + 4 + self.state().cursor_position()
+        self.label().width() + 4 + self.state().cursor_position()
     }
 
     /// Renders the prompt onto the terminal.
@@ -367,10 +503,11 @@ impl Prompt {
                 .set_background(AnsiColor::Silver)
                 .clone(),
         ));
-        changes.push(Change::Text(format!("  {} ", self.prompt)));
+        let label = self.label().into_owned();
+        changes.push(Change::Text(format!("  {} ", label)));
         changes.push(Change::AllAttributes(CellAttributes::default()));
         changes.push(Change::Text(" ".into()));
-        let offset = self.prompt.width() + 4;
+        let offset = label.width() + 4;
         self.state_mut().render(changes, offset, width)?;
         Ok(())
     }
@@ -385,29 +522,52 @@ impl Prompt {
         const CTRL: Modifiers = Modifiers::CTRL;
         const NONE: Modifiers = Modifiers::NONE;
         const ALT: Modifiers = Modifiers::ALT;
-        let value_width = width - self.prompt.width() - 4;
+        if let Some(ref options) = self.options {
+            if options.dispatch_key(&key) {
+                return Ok(self.changed().or(Some(Action::RefreshPrompt)));
+            }
+        }
+        let old_value = self.change.as_ref().map(|_| self.state().value.clone());
+        let value_width = width - self.label().width() - 4;
         let action = match (key.modifiers, key.key) {
             (NONE, Enter) | (CTRL, Char('J')) | (CTRL, Char('M')) => {
                 // Finish.
-                let _ = self.history.save();
-                let mut run = self.run.take();
-                let value: String = self.state().value[..].iter().collect();
+                return Ok(Some(self.finish()));
+            }
+            (NONE, Escape) => {
+                // Cancel.
+                let mut cancel = self.cancel.take();
                 return Ok(Some(Action::Run(Box::new(move |screen: &mut Screen| {
                     screen.clear_prompt();
-                    if let Some(ref mut run) = run {
-                        run(screen, &value)
+                    if let Some(ref mut cancel) = cancel {
+                        cancel(screen)
                     } else {
                         Ok(Some(Action::Render))
                     }
                 }))));
             }
-            (NONE, Escape) => {
-                // Cancel.
-                return Ok(Some(Action::Run(Box::new(|screen: &mut Screen| {
+            (CTRL, Char('D')) if self.end_of_input.is_some() && self.state().value.is_empty() => {
+                // End of input.
+                let mut end_of_input = self.end_of_input.take();
+                return Ok(Some(Action::Run(Box::new(move |screen: &mut Screen| {
                     screen.clear_prompt();
-                    Ok(Some(Action::Render))
+                    match end_of_input {
+                        Some(ref mut end_of_input) => end_of_input(screen),
+                        None => Ok(Some(Action::Render)),
+                    }
                 }))));
             }
+            (NONE, Char(c)) if self.single_key => {
+                self.state_mut().value = vec![c];
+                return Ok(Some(self.finish()));
+            }
+            (NONE, Tab) if self.complete.is_some() => {
+                let value: String = self.state().value[..].iter().collect();
+                match self.complete.as_ref().and_then(|complete| complete(&value)) {
+                    Some(completed) => self.state_mut().set_value(&completed),
+                    None => None,
+                }
+            }
             (NONE, Char(c)) => self.state_mut().insert_char(c, value_width),
             (NONE, Backspace) | (CTRL, Char('H')) => self.state_mut().delete_prev_char(),
             (NONE, Delete) | (CTRL, Char('D')) => self.state_mut().delete_next_char(),
@@ -427,15 +587,20 @@ impl Prompt {
             _ => return Ok(None),
         };
         self.state_mut().clamp_offset(value_width);
+        if let Some(old_value) = old_value {
+            if old_value != self.state().value {
+                return Ok(self.changed());
+            }
+        }
         Ok(action)
     }
 
     /// Paste some text into the prompt.
     pub(crate) fn paste(&mut self, text: &str, width: usize) -> Result<Option<Action>, Error> {
-        let value_width = width - self.prompt.width() - 4;
+        let value_width = width - self.label().width() - 4;
         let action = self.state_mut().insert_str(text);
         self.state_mut().clamp_offset(value_width);
-        Ok(action)
+        Ok(self.changed().or(action))
     }
 }
 
@@ -0,0 +1,108 @@
+//! Pseudo-terminals.
+//!
+//! Commands can be run with their output streams attached to pseudo-terminals
+//! rather than pipes, so that they behave as if they were writing to the
+//! terminal, for example by keeping their colors.
+use anyhow::Error;
+use std::fs::File as StdFile;
+use std::io::Read;
+use std::process::Stdio;
+
+/// A pseudo-terminal.
+pub(crate) struct Pty {
+    /// The master side, from which the output is read.
+    master: StdFile,
+
+    /// The slave side, which the command writes to.
+    slave: StdFile,
+}
+
+impl Pty {
+    /// Open a new pseudo-terminal with the given size.
+    ///
+    /// Output processing is disabled, so that newlines are not converted to
+    /// carriage return and newline pairs.
+    #[cfg(unix)]
+    pub(crate) fn open(cols: usize, rows: usize) -> Result<Pty, Error> {
+        use std::os::unix::io::{AsRawFd, FromRawFd};
+        use std::{io, mem, ptr};
+
+        let mut master = -1;
+        let mut slave = -1;
+        let mut size = libc::winsize {
+            ws_row: rows as u16,
+            ws_col: cols as u16,
+            ws_xpixel: 0,
+            ws_ypixel: 0,
+        };
+        let pty = unsafe {
+            if libc::openpty(
+                &mut master,
+                &mut slave,
+                ptr::null_mut(),
+                ptr::null_mut(),
+                &mut size as *mut libc::winsize,
+            ) != 0
+            {
+                return Err(io::Error::last_os_error().into());
+            }
+            Pty {
+                master: StdFile::from_raw_fd(master),
+                slave: StdFile::from_raw_fd(slave),
+            }
+        };
+        // Don't let other commands inherit the pseudo-terminal.  The command
+        // that uses it gets its own copy of the slave side as its output.
+        for fd in [pty.master.as_raw_fd(), pty.slave.as_raw_fd()] {
+            unsafe {
+                if libc::fcntl(fd, libc::F_SETFD, libc::FD_CLOEXEC) != 0 {
+                    return Err(io::Error::last_os_error().into());
+                }
+            }
+        }
+        unsafe {
+            let mut termios: libc::termios = mem::zeroed();
+            if libc::tcgetattr(pty.slave.as_raw_fd(), &mut termios) != 0 {
+                return Err(io::Error::last_os_error().into());
+            }
+            termios.c_oflag &= !libc::OPOST;
+            if libc::tcsetattr(pty.slave.as_raw_fd(), libc::TCSANOW, &termios) != 0 {
+                return Err(io::Error::last_os_error().into());
+            }
+        }
+        Ok(pty)
+    }
+
+    /// Pseudo-terminals are not supported on this platform.
+    #[cfg(not(unix))]
+    pub(crate) fn open(_cols: usize, _rows: usize) -> Result<Pty, Error> {
+        anyhow::bail!("pseudo-terminals are not supported on this platform")
+    }
+
+    /// Returns a handle to the slave side, for use as a command's output.
+    pub(crate) fn stdio(&self) -> Result<Stdio, Error> {
+        Ok(Stdio::from(self.slave.try_clone()?))
+    }
+
+    /// Close the slave side, and return a reader for the output written to
+    /// it.  This must be called once the command has started, so that the
+    /// reader reaches the end of the output when the command exits.
+    pub(crate) fn into_reader(self) -> PtyReader {
+        PtyReader(self.master)
+    }
+}
+
+/// Reads the output written to a pseudo-terminal.
+pub(crate) struct PtyReader(StdFile);
+
+impl Read for PtyReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        match self.0.read(buf) {
+            // Once all handles to the slave side are closed, reading from the
+            // master side fails with `EIO` rather than returning end of file.
+            #[cfg(unix)]
+            Err(ref e) if e.raw_os_error() == Some(libc::EIO) => Ok(0),
+            result => result,
+        }
+    }
+}
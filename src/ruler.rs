@@ -2,7 +2,7 @@
 use std::cmp::{max, min};
 use std::fmt::Write;
 use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
-use std::sync::Arc;
+use std::sync::{Arc, RwLock};
 use std::time::Instant;
 use termwiz::surface::change::Change;
 use unicode_width::UnicodeWidthStr;
@@ -10,10 +10,13 @@ use unicode_width::UnicodeWidthStr;
 use crate::bar::{Bar, BarItem, BarString, BarStyle};
 use crate::config::WrappingMode;
 use crate::file::File;
+use crate::search::SearchProgress;
 use crate::util;
 
 pub(crate) struct Ruler {
     position: Arc<PositionIndicator>,
+    matches: Arc<MatchIndicator>,
+    filter: Arc<FilterIndicator>,
     loading: Arc<LoadingIndicator>,
     ruler_bar: Bar,
 }
@@ -23,16 +26,22 @@ impl Ruler {
         let title = Arc::new(BarString::new(file.title().to_string()));
         let file_info = Arc::new(FileInfo::new(file.clone()));
         let position = Arc::new(PositionIndicator::new(file.clone()));
+        let matches = Arc::new(MatchIndicator::new());
+        let filter = Arc::new(FilterIndicator::new());
         let loading = Arc::new(LoadingIndicator::new(file));
 
         let mut ruler_bar = Bar::new(BarStyle::Normal);
         ruler_bar.add_left_item(title);
         ruler_bar.add_right_item(file_info);
+        ruler_bar.add_right_item(matches.clone());
+        ruler_bar.add_right_item(filter.clone());
         ruler_bar.add_right_item(position.clone());
         ruler_bar.add_right_item(loading.clone());
 
         Ruler {
             position,
+            matches,
+            filter,
             loading,
             ruler_bar,
         }
@@ -68,6 +77,14 @@ impl Ruler {
             .following_end
             .store(following_end, Ordering::SeqCst);
     }
+
+    pub(crate) fn set_search(&self, search: Option<SearchProgress>) {
+        *self.matches.search.write().unwrap() = search;
+    }
+
+    pub(crate) fn set_filter(&self, filter: Option<String>) {
+        *self.filter.description.write().unwrap() = filter;
+    }
 }
 
 /// Shows the file's additional info.
@@ -95,6 +112,82 @@ impl BarItem for FileInfo {
     }
 }
 
+/// Shows the current match and the progress of the search, if any.
+struct MatchIndicator {
+    search: RwLock<Option<SearchProgress>>,
+}
+
+impl MatchIndicator {
+    fn new() -> Self {
+        MatchIndicator {
+            search: RwLock::new(None),
+        }
+    }
+
+    fn content(&self) -> Option<String> {
+        let search = self.search.read().unwrap();
+        let search = search.as_ref()?;
+        let finished = search.finished();
+        let mut out = match search.current_match_index() {
+            Some(index) => format!(
+                "match {}/{} ({} lines)",
+                index + 1,
+                search.match_count(),
+                search.matching_line_count(),
+            ),
+            None if finished => String::from("no matches"),
+            None => String::new(),
+        };
+        if !finished {
+            if !out.is_empty() {
+                out.push(' ');
+            }
+            out.push_str("[searching]");
+        }
+        Some(out)
+    }
+}
+
+impl BarItem for MatchIndicator {
+    fn width(&self) -> usize {
+        self.content().map(|content| content.width()).unwrap_or(0)
+    }
+
+    fn render(&self, changes: &mut Vec<Change>, width: usize) {
+        if let Some(content) = self.content() {
+            changes.push(Change::Text(util::truncate_string(content, 0, width)));
+        }
+    }
+}
+
+/// Shows the active filter, if any.
+struct FilterIndicator {
+    description: RwLock<Option<String>>,
+}
+
+impl FilterIndicator {
+    fn new() -> Self {
+        FilterIndicator {
+            description: RwLock::new(None),
+        }
+    }
+}
+
+impl BarItem for FilterIndicator {
+    fn width(&self) -> usize {
+        match *self.description.read().unwrap() {
+            Some(ref description) => description.width(),
+            None => 0,
+        }
+    }
+
+    fn render(&self, changes: &mut Vec<Change>, width: usize) {
+        if let Some(ref description) = *self.description.read().unwrap() {
+            changes.push(Change::Text(util::truncate_string(description, 0, width)));
+        }
+    }
+}
+
 /// Indicates the current position within the file.
 struct PositionIndicator {
     file: File,
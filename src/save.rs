@@ -0,0 +1,253 @@
+//! Saving files.
+//!
+//! The content of a file can be saved to disk.  Saving runs in a background
+//! thread, which optionally waits for streamed input to finish, and reports
+//! its outcome with a `Saved` event.  Saves can be cancelled while they are
+//! running, which is the only way to stop waiting for an endless stream.
+//!
+//! The content is written to a temporary file next to the destination, which
+//! replaces the destination once it is complete.
+use anyhow::{bail, Context, Error};
+use regex::bytes::NoExpand;
+use std::borrow::Cow;
+use std::cell::Cell;
+use std::fs::{self, File as StdFile};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+use termwiz::input::{KeyCode, KeyEvent, Modifiers};
+
+use crate::event::{Event, EventSender};
+use crate::file::{File, DEFAULT_NEEDED_LINES};
+use crate::overstrike;
+use crate::prompt::PromptOptions;
+use crate::search::ESCAPE_SEQUENCE;
+
+/// How long to wait between checks for more input while waiting for a
+/// streamed file to finish.
+const WAIT_INTERVAL: Duration = Duration::from_millis(100);
+
+/// A save running in a background thread.  Dropping it cancels the save.
+pub(crate) struct Saving {
+    state: Arc<SavingState>,
+}
+
+struct SavingState {
+    /// Set to stop the save.
+    cancelled: AtomicBool,
+
+    /// Set once the save has finished.
+    finished: AtomicBool,
+}
+
+impl Saving {
+    /// Returns true if the save has finished.
+    pub(crate) fn finished(&self) -> bool {
+        self.state.finished.load(Ordering::SeqCst)
+    }
+}
+
+impl Drop for Saving {
+    fn drop(&mut self) {
+        self.state.cancelled.store(true, Ordering::SeqCst);
+    }
+}
+
+/// Options that control how files are saved.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub(crate) struct SaveOptions {
+    /// Whether escape sequences are removed.
+    pub(crate) strip_escapes: bool,
+
+    /// Whether overstruck text is converted to escape sequences.
+    pub(crate) convert_overstrike: bool,
+
+    /// Whether to wait for streamed input to finish before completing.
+    pub(crate) wait: bool,
+}
+
+impl PromptOptions for Cell<SaveOptions> {
+    fn dispatch_key(&self, key: &KeyEvent) -> bool {
+        let mut options = self.get();
+        match (key.modifiers, key.key) {
+            (Modifiers::ALT, KeyCode::Char('e')) => options.strip_escapes = !options.strip_escapes,
+            (Modifiers::ALT, KeyCode::Char('o')) => {
+                options.convert_overstrike = !options.convert_overstrike
+            }
+            (Modifiers::ALT, KeyCode::Char('w')) => options.wait = !options.wait,
+            _ => return false,
+        }
+        self.set(options);
+        true
+    }
+
+    fn describe(&self) -> String {
+        let options = self.get();
+        let escapes = if options.strip_escapes {
+            "strip escapes"
+        } else {
+            "keep escapes"
+        };
+        let overstrike = if options.convert_overstrike {
+            "convert overstrike"
+        } else {
+            "keep overstrike"
+        };
+        let wait = if options.wait {
+            "wait for input"
+        } else {
+            "loaded input"
+        };
+        format!("{}, {}, {}", escapes, overstrike, wait)
+    }
+}
+
+/// Check whether `file` can be saved to `path`.
+///
+/// Saving over the file that is being viewed is refused, as its content is
+/// read from the destination.  Returns true if the destination already
+/// exists, and so would be overwritten.
+pub(crate) fn check_destination(file: &File, path: &Path) -> Result<bool, Error> {
+    if let (Some(source), Ok(destination)) = (file.path(), path.canonicalize()) {
+        if source.canonicalize().ok().as_ref() == Some(&destination) {
+            bail!("Can't save over the file being viewed");
+        }
+    }
+    Ok(path.exists())
+}
+
+/// Save the content of `file` to `path` in a background thread.
+///
+/// The temporary file is created immediately, so that errors creating it can
+/// be reported straight away.  Once saving finishes, a `Saved` event is sent
+/// with a message describing the outcome.  The save is cancelled if the
+/// returned `Saving` is dropped before then.
+pub(crate) fn save_file(
+    file: &File,
+    path: PathBuf,
+    options: SaveOptions,
+    event_sender: EventSender,
+) -> Result<Saving, Error> {
+    let directory = match path.parent() {
+        Some(directory) if !directory.as_os_str().is_empty() => directory,
+        _ => Path::new("."),
+    };
+    let temp_file = tempfile::Builder::new()
+        .prefix(".streampager-")
+        .tempfile_in(directory)
+        .with_context(|| path.to_string_lossy().into_owned())?;
+    let output = temp_file.reopen()?;
+    let state = Arc::new(SavingState {
+        cancelled: AtomicBool::new(false),
+        finished: AtomicBool::new(false),
+    });
+    thread::spawn({
+        let file = file.clone();
+        let state = state.clone();
+        move || {
+            let result = write_lines_until(&file, output, options, &state.cancelled);
+            let result = result.and_then(|lines| {
+                set_permissions(temp_file.as_file(), &path)?;
+                temp_file.persist(&path)?;
+                Ok(lines)
+            });
+            let message = match result {
+                Ok(lines) => format!("Saved {} lines to {}", lines, path.to_string_lossy()),
+                Err(e) => format!("{}: {}", path.to_string_lossy(), e),
+            };
+            state.finished.store(true, Ordering::SeqCst);
+            let _ = event_sender.send(Event::Saved(file.index(), message));
+        }
+    });
+    Ok(Saving { state })
+}
+
+/// Give the temporary file the permissions of the file it replaces.  Temporary
+/// files are only readable by their owner, so new files get the usual
+/// permissions instead.
+fn set_permissions(temp_file: &StdFile, path: &Path) -> Result<(), Error> {
+    match fs::metadata(path) {
+        Ok(metadata) => temp_file.set_permissions(metadata.permissions())?,
+        #[cfg(unix)]
+        Err(_) => {
+            use std::os::unix::fs::PermissionsExt;
+            temp_file.set_permissions(fs::Permissions::from_mode(0o644))?
+        }
+        #[cfg(not(unix))]
+        Err(_) => {}
+    }
+    Ok(())
+}
+
+/// Write the lines of `file` to `output`.
+///
+/// Returns the number of lines written.
+pub(crate) fn write_lines(
+    file: &File,
+    output: StdFile,
+    options: SaveOptions,
+) -> Result<usize, Error> {
+    write_lines_until(file, output, options, &AtomicBool::new(false))
+}
+
+/// Write the lines of `file` to `output`, stopping with an error if
+/// `cancelled` is set.
+///
+/// When waiting for streamed input, more lines are requested as they are
+/// written, so the stream is only loaded for as long as the save runs.
+fn write_lines_until(
+    file: &File,
+    output: StdFile,
+    options: SaveOptions,
+    cancelled: &AtomicBool,
+) -> Result<usize, Error> {
+    let mut output = BufWriter::new(output);
+    let mut written = 0;
+    loop {
+        if cancelled.load(Ordering::SeqCst) {
+            bail!("Save cancelled");
+        }
+        // Check whether the file is loaded before counting the lines, so that
+        // the final line is only written once it is complete.
+        let loaded = file.loaded();
+        let lines = file.lines();
+        let complete_lines = if loaded || !options.wait {
+            lines
+        } else {
+            lines.saturating_sub(1)
+        };
+        let progressed = written < complete_lines;
+        while written < complete_lines {
+            file.with_line(written, |data| write_line(&mut output, &data, options))
+                .unwrap_or(Ok(()))?;
+            written += 1;
+        }
+        if loaded || !options.wait {
+            break;
+        }
+        file.set_needed_lines(lines + DEFAULT_NEEDED_LINES);
+        if !progressed {
+            thread::sleep(WAIT_INTERVAL);
+        }
+    }
+    output.flush()?;
+    Ok(written)
+}
+
+/// Write a single line to `output`, converting it according to `options`.
+fn write_line(output: &mut impl Write, data: &[u8], options: SaveOptions) -> Result<(), Error> {
+    let data = if options.convert_overstrike {
+        overstrike::convert_overstrike(data)
+    } else {
+        Cow::Borrowed(data)
+    };
+    if options.strip_escapes {
+        output.write_all(&ESCAPE_SEQUENCE.replace_all(&data, NoExpand(b"")))?;
+    } else {
+        output.write_all(&data)?;
+    }
+    Ok(())
+}
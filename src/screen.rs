@@ -26,8 +26,12 @@
 //!
 //! ```
 
-use anyhow::Error;
+use anyhow::{bail, Error};
+use std::cell::Cell;
 use std::cmp::{max, min};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::rc::Rc;
 use std::sync::Arc;
 use termwiz::cell::{CellAttributes, Intensity};
 use termwiz::color::{AnsiColor, ColorAttribute};
@@ -41,18 +45,25 @@ use crate::config::{Config, WrappingMode};
 use crate::display::Action;
 use crate::display::Capabilities;
 use crate::event::EventSender;
-use crate::file::File;
-use crate::line::Line;
+use crate::file::{File, FileLines, Signal};
+use crate::filter::Filter;
+use crate::highlight::{self, Highlight};
+use crate::line::{Line, SearchMatches};
 use crate::line_cache::LineCache;
 use crate::progress::Progress;
 use crate::prompt::Prompt;
 use crate::refresh::Refresh;
 use crate::ruler::Ruler;
-use crate::search::{MatchMotion, Search, SearchKind};
+use crate::save::{self, SaveOptions, Saving};
+use crate::search::{trim_trailing_newline, MatchMotion, Search, SearchKind, SearchOptions};
+use crate::session::Session;
 use crate::util::number_width;
 
 const LINE_CACHE_SIZE: usize = 1000;
 
+/// The number of positions that are kept in the jump list.
+const JUMP_LIST_SIZE: usize = 100;
+
 /// The state of the previous render.
 #[derive(Clone, Debug, Default)]
 struct RenderState {
@@ -62,13 +73,15 @@ struct RenderState {
     /// The number of rows on screen.
     height: usize,
 
-    /// The file line at the top of the screen.
+    /// The line at the top of the screen.  When a filter is active, this and
+    /// the other line indexes in the render state refer to lines in the
+    /// filtered view rather than lines in the file.
     top_line: usize,
 
-    /// The porition of the file line at the top of the screen.
+    /// The porition of the line at the top of the screen.
     top_line_portion: usize,
 
-    /// The file line at the bottom of the screen.
+    /// The line at the bottom of the screen.
     bottom_line: usize,
 
     /// The column at the left of the screen.
@@ -77,7 +90,7 @@ struct RenderState {
     /// The height of the overlay.
     overlay_height: usize,
 
-    /// The number of lines in the file.
+    /// The number of lines in view.
     file_lines: usize,
 
     /// The number of searched lines.
@@ -108,13 +121,13 @@ struct RenderState {
     /// The row search status was rendered to.
     search_row: Option<usize>,
 
-    /// The start and end row of each file line in view.
+    /// The start and end row of each line in view.
     file_line_rows: Vec<(usize, usize)>,
 }
 
 impl RenderState {
-    /// Returns the start and end row of the file line on the screen, if the
-    /// file line is currently visible.
+    /// Returns the start and end row of the line on the screen, if the line
+    /// is currently visible.
     fn file_line_rows(&self, file_line_index: usize) -> Option<(usize, usize)> {
         if file_line_index >= self.top_line && file_line_index < self.bottom_line {
             self.file_line_rows
@@ -126,6 +139,35 @@ impl RenderState {
     }
 }
 
+/// A position within the file that can be returned to.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub(crate) struct SavedPosition {
+    /// The file line at the top of the screen.
+    top_line: usize,
+
+    /// The portion of the file line at the top of the screen.
+    top_line_portion: usize,
+
+    /// Whether the screen was following the end of the file.
+    following_end: bool,
+}
+
+/// A range of lines that can be piped to a command.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub(crate) enum PipeRange {
+    /// The lines shown on the screen.
+    Screen,
+
+    /// The lines from a mark to the screen.
+    Mark(char),
+
+    /// The lines that match the current search.
+    Matches,
+
+    /// The whole file.
+    File,
+}
+
 /// A screen that is displaying a single file.
 pub(crate) struct Screen {
     /// The file being displayed.
@@ -179,6 +221,49 @@ pub(crate) struct Screen {
     /// The current ongoing search.
     search: Option<Search>,
 
+    /// Whether the current search is part of a search across all files.
+    search_all_files: bool,
+
+    /// Whether the current search is an incremental search whose prompt is
+    /// still open, and so should scroll to its first match without jumping.
+    search_incremental: bool,
+
+    /// Whether the current search was restored from a previous session or
+    /// carried over from a previous run of a command, and so should not move
+    /// to its first match.
+    search_restored: bool,
+
+    /// The current filter.
+    filter: Option<Filter>,
+
+    /// Patterns that are highlighted wherever they appear.
+    highlights: Vec<Highlight>,
+
+    /// Marked lines, by the character they are marked with.
+    marks: BTreeMap<char, usize>,
+
+    /// The output of the previous run of the command being displayed, when
+    /// lines that have changed since then are marked.
+    previous_file: Option<File>,
+
+    /// Positions to return to with `JumpBack`, most recent last.
+    jumps_back: Vec<SavedPosition>,
+
+    /// Positions to return to with `JumpForward`, most recent last.
+    jumps_forward: Vec<SavedPosition>,
+
+    /// The options for new searches and filters.  These are shared with
+    /// any open search prompt, which can change them.
+    search_options: Rc<Cell<SearchOptions>>,
+
+    /// The options for saving the file.  These are shared with any open save
+    /// prompt, which can change them.
+    save_options: Rc<Cell<SaveOptions>>,
+
+    /// Saves of the file that may still be running.  Cancelling clears
+    /// them, which stops any that are waiting for more input.
+    saves: Vec<Saving>,
+
     /// The ruler.
     ruler: Ruler,
 
@@ -201,8 +286,25 @@ pub(crate) struct Screen {
 
 impl Screen {
     /// Create a screen that displays a file.
-    pub(crate) fn new(file: File, config: Arc<Config>) -> Result<Screen, Error> {
-        Ok(Screen {
+    pub(crate) fn new(
+        file: File,
+        config: Arc<Config>,
+        event_sender: &EventSender,
+    ) -> Result<Screen, Error> {
+        let search_options = SearchOptions::new(&config);
+        let mut highlights = Vec::new();
+        let mut error = None;
+        for rule in config.highlight.iter() {
+            let color = match rule.color {
+                Some(ref color) => highlight::parse_color(color),
+                None => Ok(Highlight::default_color(highlights.len())),
+            };
+            match color.and_then(|color| Highlight::new(&rule.pattern, search_options, color)) {
+                Ok(highlight) => highlights.push(highlight),
+                Err(e) => error = Some(format!("highlight {:?}: {}", rule.pattern, e)),
+            }
+        }
+        let mut screen = Screen {
             error_file: None,
             progress: None,
             keymap: config.keymap.load()?,
@@ -216,9 +318,21 @@ impl Screen {
             line_numbers: false,
             line_cache: LineCache::new(LINE_CACHE_SIZE),
             search_line_cache: LineCache::new(LINE_CACHE_SIZE),
-            error: None,
+            error,
             prompt: None,
             search: None,
+            search_all_files: false,
+            search_incremental: false,
+            search_restored: false,
+            filter: None,
+            highlights,
+            marks: BTreeMap::new(),
+            previous_file: None,
+            jumps_back: Vec::new(),
+            jumps_forward: Vec::new(),
+            search_options: Rc::new(Cell::new(search_options)),
+            save_options: Rc::new(Cell::new(SaveOptions::default())),
+            saves: Vec::new(),
             ruler: Ruler::new(file.clone()),
             following_end: false,
             pending_absolute_scroll: None,
@@ -226,7 +340,109 @@ impl Screen {
             pending_refresh: Refresh::None,
             config,
             file,
-        })
+        };
+        if screen.config.restore_session {
+            if let Some(session) = screen.file.path().and_then(Session::load) {
+                screen.restore_session(session, event_sender);
+            }
+        }
+        Ok(screen)
+    }
+
+    /// Restore the state of the screen from a previous session.
+    fn restore_session(&mut self, session: Session, event_sender: &EventSender) {
+        self.top_line = session.top_line;
+        self.wrapping_mode = session.wrapping_mode;
+        self.line_numbers = session.line_numbers;
+        if let Some((pattern, options)) = session.search {
+            if let Ok(search) = Search::new(
+                &self.file,
+                &pattern,
+                options,
+                SearchKind::FirstAfter(session.top_line),
+                event_sender.clone(),
+            ) {
+                self.set_search(Some(search));
+                self.search_restored = true;
+            }
+        }
+    }
+
+    /// Save the state of the screen so that it can be restored the next time
+    /// the file is opened.
+    pub(crate) fn save_session(&self) -> Result<(), Error> {
+        if let (true, Some(path)) = (self.config.restore_session, self.file.path()) {
+            let session = Session {
+                top_line: self.save_position().top_line,
+                wrapping_mode: self.wrapping_mode,
+                line_numbers: self.line_numbers,
+                search: self
+                    .search
+                    .as_ref()
+                    .map(|search| (search.pattern().to_string(), search.options())),
+            };
+            session.save(path)?;
+        }
+        Ok(())
+    }
+
+    /// Replace the file being displayed with the output of a new run of the
+    /// same command, keeping the position, search and filter.
+    ///
+    /// If `mark_changes` is true, or changes were already being marked,
+    /// lines that differ from the previous run are marked.
+    pub(crate) fn replace_file(
+        &mut self,
+        file: File,
+        mark_changes: bool,
+        event_sender: &EventSender,
+    ) -> Result<(), Error> {
+        let top_line = self.file_line_index(self.top_line);
+        let previous_file = std::mem::replace(&mut self.file, file);
+        if mark_changes || self.previous_file.is_some() {
+            self.previous_file = Some(previous_file);
+        }
+        self.ruler = Ruler::new(self.file.clone());
+        self.flush_line_caches();
+        if let Some(search) = self.search.take() {
+            let search_all_files = self.search_all_files;
+            let search = Search::new(
+                &self.file,
+                search.pattern(),
+                search.options(),
+                SearchKind::FirstAfter(top_line),
+                event_sender.clone(),
+            )?;
+            self.set_search(Some(search));
+            self.search_all_files = search_all_files;
+            self.search_restored = true;
+        }
+        if let Some(filter) = self.filter.take() {
+            self.filter = Some(filter.for_file(&self.file, event_sender.clone())?);
+            self.filter_changed(top_line);
+        }
+        self.refresh();
+        Ok(())
+    }
+
+    /// Returns true if line `line_index` of the file is different from the
+    /// same line in the output of the previous run of the command.  Lines
+    /// that haven't been loaded for both runs yet are not known to have
+    /// changed, unless the previous run finished without reaching them.
+    fn line_changed(&self, line_index: usize) -> bool {
+        match self.previous_file {
+            Some(ref previous_file) => {
+                let changed = previous_file.with_line(line_index, |previous_data| {
+                    self.file
+                        .with_line(line_index, |data| data != previous_data)
+                });
+                match changed {
+                    Some(changed) => changed.unwrap_or(false),
+                    None => previous_file.loaded() && line_index < self.file.lines(),
+                }
+            }
+            None => false,
+        }
     }
 
     /// Resize the screen
@@ -260,11 +476,18 @@ impl Screen {
         // Hide the cursor while we render things.
         changes.push(Change::CursorShape(CursorShape::Hidden));
 
+        // Collect any new lines that have passed the filter.
+        if self.filter.as_mut().map(Filter::update).unwrap_or(false) {
+            self.refresh_ruler();
+        }
+        self.ruler
+            .set_filter(self.filter.as_ref().map(Filter::description));
+
         // Set up the render state.
         let mut render: RenderState = RenderState::default();
         render.width = self.width;
         render.height = self.height;
-        render.file_lines = self.file.lines();
+        render.file_lines = self.view_lines();
         render.error_file_lines = self.error_file.as_ref().map(|f| f.lines()).unwrap_or(0);
         if let Some(search) = self.search.as_ref() {
             render.searched_lines = search.searched_lines();
@@ -272,7 +495,9 @@ impl Screen {
         let mut pending_refresh = self.pending_refresh.clone();
         let file_loaded = self.file.loaded();
         let file_width = if self.line_numbers {
-            render.width - number_width(render.file_lines) - 2
+            render.width - number_width(self.file.lines()) - 2
+        } else if self.previous_file.is_some() {
+            render.width.saturating_sub(1)
         } else {
             render.width
         };
@@ -378,7 +603,11 @@ impl Screen {
             let mut remaining = file_view_height;
             while top_line > 0 && remaining > 0 {
                 top_line -= 1;
-                if let Some(line) = self.line_cache.get_or_create(&self.file, top_line, None) {
+                let file_line = self.file_line_index(top_line);
+                if let Some(line) =
+                    self.line_cache
+                        .get_or_create(&self.file, file_line, None, &self.highlights)
+                {
                     let line_height = line.height(file_width, self.wrapping_mode);
                     if line_height > remaining {
                         top_line_portion = line_height - remaining;
@@ -399,7 +628,10 @@ impl Screen {
                 let mut scroll_line = self.top_line;
                 let mut scroll_line_portion = self.top_line_portion;
                 while scroll_line < end_top_line {
-                    if let Some(line) = self.line_cache.get_or_create(&self.file, scroll_line, None)
+                    let file_line = self.file_line_index(scroll_line);
+                    if let Some(line) =
+                        self.line_cache
+                            .get_or_create(&self.file, file_line, None, &self.highlights)
                     {
                         let line_height = line.height(file_width, self.wrapping_mode);
                         scroll_by += line_height.saturating_sub(scroll_line_portion);
@@ -425,9 +657,23 @@ impl Screen {
             }
         }
 
-        // Perform pending absolute scroll
-        if let Some(line) = self.pending_absolute_scroll.take() {
-            self.top_line = line;
+        // Perform pending absolute scroll.  If the filter has not yet reached
+        // the target line, wait until it does.
+        let waiting_for_filter = match (self.pending_absolute_scroll, self.filter.as_ref()) {
+            (Some(file_line), Some(filter)) => {
+                !filter.finished()
+                    && filter.checked_lines() <= file_line
+                    && filter.checked_lines() + 1 < self.file.lines()
+            }
+            _ => false,
+        };
+        let pending_absolute_scroll = if waiting_for_filter {
+            None
+        } else {
+            self.pending_absolute_scroll.take()
+        };
+        if let Some(file_line) = pending_absolute_scroll {
+            self.top_line = self.view_line_index(file_line);
             self.top_line_portion = 0;
             pending_refresh.add_range(0, file_view_height);
             // Scroll up so that the target line is in the center of the
@@ -458,7 +704,11 @@ impl Screen {
             while scroll_up > 0 && top_line > 0 {
                 top_line -= 1;
                 top_line_portion = 0;
-                if let Some(line) = self.line_cache.get_or_create(&self.file, top_line, None) {
+                let file_line = self.file_line_index(top_line);
+                if let Some(line) =
+                    self.line_cache
+                        .get_or_create(&self.file, file_line, None, &self.highlights)
+                {
                     let line_height = line.height(file_width, self.wrapping_mode);
                     if line_height > scroll_up {
                         scroll_distance += scroll_up;
@@ -478,8 +728,10 @@ impl Screen {
             let mut top_line_portion = self.top_line_portion;
             let (max_top_line, max_top_line_portion) = if self.config.scroll_past_eof {
                 let last_line = render.file_lines.saturating_sub(1);
+                let file_line = self.file_line_index(last_line);
                 let line_height = if let Some(line) =
-                    self.line_cache.get_or_create(&self.file, last_line, None)
+                    self.line_cache
+                        .get_or_create(&self.file, file_line, None, &self.highlights)
                 {
                     line.height(file_width, self.wrapping_mode)
                 } else {
@@ -492,7 +744,11 @@ impl Screen {
             while scroll_down > 0
                 && (top_line, top_line_portion) < (max_top_line, max_top_line_portion)
             {
-                if let Some(line) = self.line_cache.get_or_create(&self.file, top_line, None) {
+                let file_line = self.file_line_index(top_line);
+                if let Some(line) =
+                    self.line_cache
+                        .get_or_create(&self.file, file_line, None, &self.highlights)
+                {
                     let line_height = line.height(file_width, self.wrapping_mode);
                     let line_height_remaining = line_height.saturating_sub(top_line_portion);
                     if line_height_remaining > scroll_down {
@@ -567,8 +823,12 @@ impl Screen {
             let mut file_line_rows = Vec::new();
             let mut row = 0;
             let mut top_portion = render.top_line_portion;
-            for file_line in render.top_line..render.file_lines {
-                if let Some(line) = self.line_cache.get_or_create(&self.file, file_line, None) {
+            for view_line in render.top_line..render.file_lines {
+                let file_line = self.file_line_index(view_line);
+                if let Some(line) =
+                    self.line_cache
+                        .get_or_create(&self.file, file_line, None, &self.highlights)
+                {
                     let line_height = line.height(file_width, self.wrapping_mode);
                     let visible_line_height = min(
                         line_height.saturating_sub(top_portion),
@@ -597,10 +857,15 @@ impl Screen {
 
         // Update the ruler with the new position.
         self.ruler.set_position(
-            render.top_line,
+            self.file_line_index(render.top_line),
             render.left,
             if !self.following_end {
-                Some(render.bottom_line)
+                Some(
+                    render
+                        .bottom_line
+                        .checked_sub(1)
+                        .map_or(0, |line| self.file_line_index(line) + 1),
+                )
             } else {
                 None
             },
@@ -629,11 +894,15 @@ impl Screen {
             // What needs to be refreshed because search has progressed?
             if let Some(search) = self.search.as_ref() {
                 if render.searched_lines > self.rendered.searched_lines {
-                    let start_line = max(render.top_line, self.rendered.searched_lines);
-                    let end_line = min(render.bottom_line, render.searched_lines);
-                    for line in search.matching_lines(start_line, end_line).into_iter() {
-                        if let Some((start_row, end_row)) = render.file_line_rows(line) {
-                            pending_refresh.add_range(start_row, end_row);
+                    for view_line in render.top_line..render.bottom_line {
+                        let file_line = self.file_line_index(view_line);
+                        if file_line >= self.rendered.searched_lines
+                            && file_line < render.searched_lines
+                            && search.line_matches(file_line)
+                        {
+                            if let Some((start_row, end_row)) = render.file_line_rows(view_line) {
+                                pending_refresh.add_range(start_row, end_row);
+                            }
                         }
                     }
                 }
@@ -774,24 +1043,30 @@ impl Screen {
         left: usize,
         width: usize,
     ) -> Result<(), Error> {
+        let changed = self.line_changed(line_index);
         let line = match self.search {
-            Some(ref search) if search.line_matches(line_index) => self
-                .search_line_cache
-                .get_or_create(&self.file, line_index, Some(search.regex())),
-            _ => self.line_cache.get_or_create(&self.file, line_index, None),
+            Some(ref search) if search.line_matches(line_index) => {
+                let ranges = search.multiline_ranges(line_index);
+                let matches = match ranges {
+                    Some(ref ranges) => SearchMatches::Ranges(ranges),
+                    None => SearchMatches::Regex(search.regex()),
+                };
+                self.search_line_cache.get_or_create(
+                    &self.file,
+                    line_index,
+                    Some(matches),
+                    &self.highlights,
+                )
+            }
+            _ => self
+                .line_cache
+                .get_or_create(&self.file, line_index, None, &self.highlights),
         };
 
         let match_index = self
             .search
             .as_ref()
-            .and_then(|ref search| search.current_match())
-            .and_then(|(match_line_index, match_index)| {
-                if match_line_index == line_index {
-                    Some(match_index)
-                } else {
-                    None
-                }
-            });
+            .and_then(|search| search.current_match_in_line(line_index));
 
         if let Some(line) = line {
             changes.push(Change::CursorPosition {
@@ -808,17 +1083,42 @@ impl Screen {
                     changes.push(Change::AllAttributes(
                         CellAttributes::default()
                             .set_foreground(AnsiColor::Black)
-                            .set_background(AnsiColor::Silver)
+                            .set_background(if changed {
+                                AnsiColor::Olive
+                            } else {
+                                AnsiColor::Silver
+                            })
                             .clone(),
                     ));
                     if portion == 0 {
-                        changes.push(Change::Text(format!(" {:>1$} ", line_index + 1, lw)));
+                        let mark = self
+                            .marks
+                            .iter()
+                            .find(|&(_, &mark_line)| mark_line == line_index)
+                            .map(|(&mark, _)| mark)
+                            .unwrap_or(' ');
+                        changes.push(Change::Text(format!("{}{:>2$} ", mark, line_index + 1, lw)));
                     } else {
                         changes.push(Change::Text(" ".repeat(lw + 2)));
                     };
                     changes.push(Change::AllAttributes(CellAttributes::default()));
                     end -= lw + 2;
                 }
+            } else if self.previous_file.is_some() && width > 1 {
+                // Show a bar next to lines that have changed.
+                if changed {
+                    changes.push(Change::AllAttributes(
+                        CellAttributes::default()
+                            .set_foreground(AnsiColor::Black)
+                            .set_background(AnsiColor::Olive)
+                            .clone(),
+                    ));
+                    changes.push(Change::Text(if portion == 0 { "+" } else { " " }.into()));
+                    changes.push(Change::AllAttributes(CellAttributes::default()));
+                } else {
+                    changes.push(Change::Text(" ".into()));
+                }
+                end -= 1;
             }
             if self.wrapping_mode == WrappingMode::Unwrapped {
                 line.render(changes, start, end, match_index)?;
@@ -961,15 +1261,24 @@ impl Screen {
 
     /// Refresh a file line.
     pub(crate) fn refresh_file_line(&mut self, file_line_index: usize) {
-        if let Some((start_row, end_row)) = self.rendered.file_line_rows(file_line_index) {
+        let view_line_index = self.view_line_index(file_line_index);
+        if self.file_line_index(view_line_index) != file_line_index {
+            // The line is hidden by the filter.
+            return;
+        }
+        if let Some((start_row, end_row)) = self.rendered.file_line_rows(view_line_index) {
             self.pending_refresh.add_range(start_row, end_row);
         }
     }
 
     /// Refresh the line with the current match (if any).
     pub(crate) fn refresh_matched_line(&mut self) {
-        if let Some(ref search) = self.search {
-            if let Some((line_index, _match_index)) = search.current_match() {
+        let lines = self
+            .search
+            .as_ref()
+            .and_then(|search| search.current_match_lines());
+        if let Some((start_line, end_line)) = lines {
+            for line_index in start_line..=end_line {
                 self.refresh_file_line(line_index);
             }
         }
@@ -977,11 +1286,10 @@ impl Screen {
 
     /// Refresh all lines with any matches.
     pub(crate) fn refresh_matched_lines(&mut self) {
+        let start_line = self.file_line_index(self.rendered.top_line);
+        let end_line = self.file_line_index(self.rendered.bottom_line);
         if let Some(ref search) = self.search {
-            for line in search
-                .matching_lines(self.rendered.top_line, self.rendered.bottom_line)
-                .into_iter()
-            {
+            for line in search.matching_lines(start_line, end_line).into_iter() {
                 self.refresh_file_line(line);
             }
         }
@@ -1071,21 +1379,57 @@ impl Screen {
                 Quit => return Ok(Some(Action::Quit)),
                 Refresh => return Ok(Some(Action::Refresh)),
                 Help => return Ok(Some(Action::ShowHelp)),
+                PromptPipe => self.prompt = Some(command::pipe()),
+                Suspend => return Ok(Some(Action::Suspend)),
+                PromptShellCommand => self.prompt = Some(command::shell_command()),
+                OpenEditor => match self.file.path() {
+                    Some(path) => {
+                        let line = self.top_file_line() + 1;
+                        return Ok(Some(Action::OpenEditor(path.to_path_buf(), line)));
+                    }
+                    None => self.prompt = Some(command::edit_temporary_file()),
+                },
+                PromptSaveFile => {
+                    self.prompt = Some(command::save_file(
+                        self.save_options.clone(),
+                        event_sender.clone(),
+                    ))
+                }
                 Cancel => {
                     self.error_file = None;
                     self.set_search(None);
+                    self.saves.clear();
                     self.error = None;
                     self.refresh();
                     return Ok(Some(Action::ClearOverlay));
                 }
                 PreviousFile => return Ok(Some(Action::PreviousFile)),
                 NextFile => return Ok(Some(Action::NextFile)),
+                ShowFileList => return Ok(Some(Action::ShowFileList)),
+                PromptOpenFile => self.prompt = Some(command::open_file()),
+                CloseFile => return Ok(Some(Action::CloseFile)),
+                RerunCommand => return Ok(Some(Action::RerunCommand)),
+                InterruptCommand => self.signal_command(Signal::Interrupt),
+                TerminateCommand => self.signal_command(Signal::Terminate),
+                KillCommand => self.signal_command(Signal::Kill),
+                PromptCommandInput => {
+                    if self.file.accepts_command_input() {
+                        self.prompt = Some(command::command_input());
+                    } else if self.file.command_running() {
+                        self.error = Some("The command's input is not open".to_string());
+                    } else {
+                        self.error = Some("No running command to send input to".to_string());
+                    }
+                }
                 ScrollUpLines(n) => self.scroll_up(n),
                 ScrollDownLines(n) => self.scroll_down(n),
                 ScrollUpScreenFraction(n) => self.scroll_up_screen_fraction(n),
                 ScrollDownScreenFraction(n) => self.scroll_down_screen_fraction(n),
-                ScrollToTop => self.scroll_to(0),
-                ScrollToBottom => self.following_end = true,
+                ScrollToTop => self.jump_to(0),
+                ScrollToBottom => {
+                    self.record_jump();
+                    self.following_end = true;
+                }
                 ScrollLeftColumns(n) => self.scroll_left(n),
                 ScrollRightColumns(n) => self.scroll_right(n),
                 ScrollLeftScreenFraction(n) => self.scroll_left_screen_fraction(n),
@@ -1099,37 +1443,457 @@ impl Screen {
                     return Ok(Some(Action::Refresh));
                 }
                 PromptGoToLine => self.prompt = Some(command::goto()),
+                JumpBack => self.jump_back(),
+                JumpForward => self.jump_forward(),
+                PromptSetMark => self.prompt = Some(command::set_mark()),
+                PromptGoToMark => self.prompt = Some(command::goto_mark()),
+                SetMark(mark) => self.set_mark(mark),
+                GoToMark(mark) => self.goto_mark(mark),
+                ShowMarks => return Ok(Some(Action::ShowMarks)),
                 PromptSearchFromStart => {
-                    self.prompt = Some(command::search(SearchKind::First, event_sender.clone()))
+                    self.prompt = Some(command::search(
+                        SearchKind::First,
+                        self.search_options(),
+                        self.config.incremental_search,
+                        event_sender.clone(),
+                    ))
                 }
                 PromptSearchForwards => {
                     self.prompt = Some(command::search(
-                        SearchKind::FirstAfter(self.rendered.top_line),
+                        SearchKind::FirstAfter(self.file_line_index(self.rendered.top_line)),
+                        self.search_options(),
+                        self.config.incremental_search,
                         event_sender.clone(),
                     ))
                 }
                 PromptSearchBackwards => {
                     self.prompt = Some(command::search(
-                        SearchKind::FirstBefore(self.rendered.bottom_line),
+                        SearchKind::FirstBefore(self.file_line_index(self.rendered.bottom_line)),
+                        self.search_options(),
+                        self.config.incremental_search,
+                        event_sender.clone(),
+                    ))
+                }
+                PromptSearchAllFiles => {
+                    self.prompt = Some(command::search_all_files(self.search_options()))
+                }
+                PromptFilter => {
+                    self.prompt = Some(command::filter(
+                        false,
+                        self.search_options(),
+                        event_sender.clone(),
+                    ))
+                }
+                PromptFilterOut => {
+                    self.prompt = Some(command::filter(
+                        true,
+                        self.search_options(),
                         event_sender.clone(),
                     ))
                 }
-                PreviousMatch => self.move_match(MatchMotion::Previous),
-                NextMatch => self.move_match(MatchMotion::Next),
-                PreviousMatchLine => self.move_match(MatchMotion::PreviousLine),
-                NextMatchLine => self.move_match(MatchMotion::NextLine),
-                FirstMatch => self.move_match(MatchMotion::First),
-                LastMatch => self.move_match(MatchMotion::Last),
+                PopFilter => self.pop_filter(),
+                PromptHighlight => self.prompt = Some(command::highlight(self.search_options())),
+                RemoveHighlight => self.remove_highlight(),
+                ClearHighlights => self.clear_highlights(),
+                ClearFilter => self.clear_filter(),
+                PreviousMatch => {
+                    if !self.move_match(MatchMotion::Previous) && self.search_all_files_done() {
+                        return Ok(Some(Action::PreviousMatchFile));
+                    }
+                }
+                NextMatch => {
+                    if !self.move_match(MatchMotion::Next) && self.search_all_files_done() {
+                        return Ok(Some(Action::NextMatchFile));
+                    }
+                }
+                PreviousMatchLine => {
+                    if !self.move_match(MatchMotion::PreviousLine) && self.search_all_files_done() {
+                        return Ok(Some(Action::PreviousMatchFile));
+                    }
+                }
+                NextMatchLine => {
+                    if !self.move_match(MatchMotion::NextLine) && self.search_all_files_done() {
+                        return Ok(Some(Action::NextMatchFile));
+                    }
+                }
+                FirstMatch => {
+                    self.move_match(MatchMotion::First);
+                }
+                LastMatch => {
+                    self.move_match(MatchMotion::Last);
+                }
                 Unrecognized(_) => {}
             }
         }
         Ok(Some(Action::Render))
     }
 
+    /// Returns the options for new searches and filters.
+    pub(crate) fn search_options(&self) -> Rc<Cell<SearchOptions>> {
+        self.search_options.clone()
+    }
+
     /// Set the search for this file.
     pub(crate) fn set_search(&mut self, search: Option<Search>) {
         self.search = search;
+        self.search_all_files = false;
+        self.search_incremental = false;
+        self.search_restored = false;
+        self.search_line_cache.clear();
+        self.ruler
+            .set_search(self.search.as_ref().map(Search::progress));
+        self.refresh_ruler();
+    }
+
+    /// Set the search for this file as an incremental search, while its
+    /// prompt is open.
+    pub(crate) fn set_incremental_search(&mut self, search: Option<Search>) {
+        self.set_search(search);
+        self.search_incremental = true;
+    }
+
+    /// Keep the incremental search once its prompt has been submitted.
+    pub(crate) fn finish_incremental_search(&mut self) {
+        self.search_incremental = false;
+    }
+
+    /// Set the search for this file as part of a search across all files.
+    pub(crate) fn set_search_all_files(&mut self, search: Search) {
+        self.set_search(Some(search));
+        self.search_all_files = true;
+    }
+
+    /// True if the current search is part of a search across all files, and
+    /// has finished searching this file, so that moving past its first or
+    /// last match moves to another file.
+    fn search_all_files_done(&self) -> bool {
+        self.search_all_files
+            && self
+                .search
+                .as_ref()
+                .map(|search| search.finished())
+                .unwrap_or(true)
+    }
+
+    /// Returns the current search.
+    pub(crate) fn search(&self) -> Option<&Search> {
+        self.search.as_ref()
+    }
+
+    /// Remove the search for this file, returning it.
+    pub(crate) fn take_search(&mut self) -> Option<Search> {
         self.search_line_cache.clear();
+        self.ruler.set_search(None);
+        self.refresh_ruler();
+        self.search.take()
+    }
+
+    /// Returns the current position in the file.
+    pub(crate) fn save_position(&self) -> SavedPosition {
+        match self.pending_absolute_scroll {
+            Some(top_line) => SavedPosition {
+                top_line,
+                top_line_portion: 0,
+                following_end: false,
+            },
+            None => SavedPosition {
+                top_line: self.file_line_index(self.top_line),
+                top_line_portion: self.top_line_portion,
+                following_end: self.following_end,
+            },
+        }
+    }
+
+    /// Returns the file line at the top of the screen.
+    pub(crate) fn top_file_line(&self) -> usize {
+        self.save_position().top_line
+    }
+
+    /// Records the current position in the jump list, before jumping
+    /// elsewhere.
+    pub(crate) fn record_jump(&mut self) {
+        self.record_jump_from(self.save_position());
+    }
+
+    /// Records a position in the jump list that has been jumped away from.
+    pub(crate) fn record_jump_from(&mut self, position: SavedPosition) {
+        if self.jumps_back.last() != Some(&position) {
+            self.push_jump_back(position);
+        }
+        self.jumps_forward.clear();
+    }
+
+    /// Adds a position to the jump list, dropping the oldest position if
+    /// the list is full.
+    fn push_jump_back(&mut self, position: SavedPosition) {
+        if self.jumps_back.len() >= JUMP_LIST_SIZE {
+            self.jumps_back.remove(0);
+        }
+        self.jumps_back.push(position);
+    }
+
+    /// Scroll to a file line, recording the current position in the jump
+    /// list.
+    pub(crate) fn jump_to(&mut self, line: usize) {
+        self.record_jump();
+        self.scroll_to(line);
+    }
+
+    /// Return to the position before the most recent jump.
+    fn jump_back(&mut self) {
+        if let Some(position) = self.jumps_back.pop() {
+            self.jumps_forward.push(self.save_position());
+            self.restore_position(position);
+        }
+    }
+
+    /// Return to the position that was left by `jump_back`.
+    fn jump_forward(&mut self) {
+        if let Some(position) = self.jumps_forward.pop() {
+            self.push_jump_back(self.save_position());
+            self.restore_position(position);
+        }
+    }
+
+    /// Marks the line at the top of the screen.
+    pub(crate) fn set_mark(&mut self, mark: char) {
+        if mark == '\'' {
+            self.error = Some(String::from("Mark ' is reserved for the previous position"));
+            return;
+        }
+        let line = self.save_position().top_line;
+        if let Some(old_line) = self.marks.insert(mark, line) {
+            self.refresh_file_line(old_line);
+        }
+        self.refresh_file_line(line);
+    }
+
+    /// Moves to a marked line.  The mark `'` returns to the position before
+    /// the most recent jump.
+    pub(crate) fn goto_mark(&mut self, mark: char) {
+        if mark == '\'' {
+            self.jump_back();
+        } else if let Some(&line) = self.marks.get(&mark) {
+            self.jump_to(line);
+        } else {
+            self.error = Some(format!("Mark {} is not set", mark));
+        }
+    }
+
+    /// Send a signal to the command whose output is being displayed.
+    fn signal_command(&mut self, signal: Signal) {
+        self.error = Some(match self.file.signal_command(signal) {
+            Ok(()) => format!("Sent {} to the command", signal),
+            Err(e) => e.to_string(),
+        });
+    }
+
+    /// Send a line of input to the command whose output is being displayed.
+    pub(crate) fn write_command_input(&mut self, line: &str) -> Result<(), Error> {
+        let mut data = line.as_bytes().to_vec();
+        data.push(b'\n');
+        self.file.write_command_input(&data)
+    }
+
+    /// Close the input of the command whose output is being displayed.
+    pub(crate) fn close_command_input(&mut self) {
+        self.error = Some(match self.file.close_command_input() {
+            Ok(()) => "Closed the command's input".to_string(),
+            Err(e) => e.to_string(),
+        });
+    }
+
+    /// Returns a list of the marks, for display in an overlay.
+    pub(crate) fn marks_text(&self) -> String {
+        if self.marks.is_empty() {
+            return String::from("No marks are set.\n");
+        }
+        let lw = number_width(self.file.lines());
+        let mut text = String::from("Marks:\n\n");
+        for (&mark, &line) in self.marks.iter() {
+            let content = self
+                .file
+                .with_line(line, |data| {
+                    let len = trim_trailing_newline(&data[..]);
+                    String::from_utf8_lossy(&data[..len]).into_owned()
+                })
+                .unwrap_or_default();
+            text.push_str(&format!("  {}  {:>3$}  {}\n", mark, line + 1, content, lw));
+        }
+        text
+    }
+
+    /// Returns the file lines in `range`.
+    pub(crate) fn pipe_lines(&self, range: PipeRange) -> Result<FileLines, Error> {
+        let top_line = self.file_line_index(self.rendered.top_line);
+        let bottom_line = self.file_line_index(self.rendered.bottom_line.saturating_sub(1));
+        let lines = match range {
+            PipeRange::Screen => FileLines::List(
+                (self.rendered.top_line..self.rendered.bottom_line)
+                    .map(|line| self.file_line_index(line))
+                    .collect(),
+            ),
+            PipeRange::Mark(mark) => match self.marks.get(&mark) {
+                Some(&line) if line <= top_line => FileLines::Range(line..bottom_line + 1),
+                Some(&line) => FileLines::Range(top_line..line + 1),
+                None => bail!("Mark {} is not set", mark),
+            },
+            PipeRange::Matches => match self.search.as_ref() {
+                Some(search) if !search.finished() => bail!("Search still running"),
+                Some(search) => FileLines::List(search.matching_lines(0, search.searched_lines())),
+                None => bail!("No search"),
+            },
+            PipeRange::File => FileLines::Range(0..self.file.lines()),
+        };
+        Ok(lines)
+    }
+
+    /// Keep track of a save of the file, so that it can be cancelled.
+    pub(crate) fn add_save(&mut self, saving: Saving) {
+        self.saves.retain(|saving| !saving.finished());
+        self.saves.push(saving);
+    }
+
+    /// Save the loaded content of the file to a temporary file, which is kept
+    /// so that it can be edited.
+    pub(crate) fn save_temporary_file(&self) -> Result<PathBuf, Error> {
+        let temp_file = tempfile::Builder::new()
+            .prefix("streampager-")
+            .suffix(".txt")
+            .tempfile()?;
+        save::write_lines(&self.file, temp_file.reopen()?, SaveOptions::default())?;
+        let (_, path) = temp_file.keep()?;
+        Ok(path)
+    }
+
+    /// Returns to a previously saved position in the file.
+    pub(crate) fn restore_position(&mut self, position: SavedPosition) {
+        self.top_line = self.view_line_index(position.top_line);
+        self.top_line_portion = position.top_line_portion;
+        self.following_end = position.following_end;
+        self.pending_absolute_scroll = None;
+        self.pending_relative_scroll = 0;
+        self.refresh();
+    }
+
+    /// Add a layer to the filter for this file.  If `exclude` is true, lines
+    /// matching the pattern are hidden, otherwise only lines matching the
+    /// pattern are shown.
+    pub(crate) fn push_filter(
+        &mut self,
+        pattern: &str,
+        exclude: bool,
+        event_sender: EventSender,
+    ) -> Result<(), Error> {
+        let top_line = self.file_line_index(self.top_line);
+        let options = self.search_options.get();
+        match self.filter {
+            Some(ref mut filter) => {
+                filter.push(&self.file, pattern, options, exclude, event_sender)?
+            }
+            None => {
+                self.filter = Some(Filter::new(
+                    &self.file,
+                    pattern,
+                    options,
+                    exclude,
+                    event_sender,
+                )?)
+            }
+        }
+        self.filter_changed(top_line);
+        Ok(())
+    }
+
+    /// Remove the most recently added layer of the filter for this file.
+    pub(crate) fn pop_filter(&mut self) {
+        let top_line = self.file_line_index(self.top_line);
+        if let Some(ref mut filter) = self.filter {
+            if !filter.pop() {
+                self.filter = None;
+            }
+            self.filter_changed(top_line);
+        }
+    }
+
+    /// Remove the filter for this file.
+    pub(crate) fn clear_filter(&mut self) {
+        let top_line = self.file_line_index(self.top_line);
+        if self.filter.take().is_some() {
+            self.filter_changed(top_line);
+        }
+    }
+
+    /// Called when the filter has changed.
+    ///
+    /// The view moves to the file line that was at the top of the screen if
+    /// it passes the new filter, otherwise to the next line that does.
+    fn filter_changed(&mut self, top_line: usize) {
+        self.top_line = 0;
+        self.top_line_portion = 0;
+        if !self.following_end {
+            self.scroll_to(top_line);
+        }
+        self.refresh();
+    }
+
+    /// Add a pattern to highlight wherever it appears, in the next unused
+    /// color.
+    pub(crate) fn add_highlight(&mut self, pattern: &str) -> Result<(), Error> {
+        let color = Highlight::default_color(self.highlights.len());
+        let highlight = Highlight::new(pattern, self.search_options.get(), color)?;
+        self.highlights.push(highlight);
+        self.flush_line_caches();
+        self.refresh();
+        Ok(())
+    }
+
+    /// Remove the most recently added highlight.
+    pub(crate) fn remove_highlight(&mut self) {
+        if self.highlights.pop().is_some() {
+            self.flush_line_caches();
+            self.refresh();
+        }
+    }
+
+    /// Remove all highlights.
+    pub(crate) fn clear_highlights(&mut self) {
+        if !self.highlights.is_empty() {
+            self.highlights.clear();
+            self.flush_line_caches();
+            self.refresh();
+        }
+    }
+
+    /// Returns the number of lines in view.  This is the number of lines in
+    /// the file, or the number of lines that pass the filter if there is one.
+    fn view_lines(&self) -> usize {
+        match self.filter {
+            Some(ref filter) => filter.lines(),
+            None => self.file.lines(),
+        }
+    }
+
+    /// Returns the index of the file line shown at the given line in view.
+    ///
+    /// Indexes beyond the end of the view map to indexes beyond the end of
+    /// the file.
+    fn file_line_index(&self, view_line_index: usize) -> usize {
+        match self.filter {
+            Some(ref filter) => filter.file_line(view_line_index).unwrap_or_else(|| {
+                self.file.lines() + view_line_index.saturating_sub(filter.lines())
+            }),
+            None => view_line_index,
+        }
+    }
+
+    /// Returns the index of the line in view that shows the given file line,
+    /// or the next line in view after it if the file line is filtered out.
+    fn view_line_index(&self, file_line_index: usize) -> usize {
+        match self.filter {
+            Some(ref filter) => filter.filtered_line(file_line_index),
+            None => file_line_index,
+        }
     }
 
     /// Set the error file for this file.
@@ -1146,17 +1910,23 @@ impl Screen {
     pub(crate) fn animate(&self) -> bool {
         self.error_file.is_some()
             || (!self.file.loaded() && !self.file.paused())
+            || self.file.command_running()
             || self.following_end
             || self
                 .search
                 .as_ref()
                 .map(|search| !search.finished())
                 .unwrap_or(false)
+            || self
+                .filter
+                .as_ref()
+                .map(|filter| !filter.finished())
+                .unwrap_or(false)
     }
 
     /// Dispatch an animation timeout, updating for the next animation frame.
     pub(crate) fn dispatch_animation(&mut self) -> Result<Option<Action>, Error> {
-        if !self.file.loaded() {
+        if !self.file.loaded() || self.file.command_running() {
             self.refresh_ruler();
         }
         if self
@@ -1166,6 +1936,10 @@ impl Screen {
             .unwrap_or(false)
         {
             self.refresh_overlay();
+            self.refresh_ruler();
+        }
+        if self.filter.as_mut().map(Filter::update).unwrap_or(false) {
+            self.refresh_ruler();
         }
         if let Some(ref error_file) = self.error_file {
             if error_file.lines() != self.rendered.error_file_lines {
@@ -1182,6 +1956,12 @@ impl Screen {
         &mut self.prompt
     }
 
+    /// Opens a prompt on the screen.
+    pub(crate) fn set_prompt(&mut self, prompt: Prompt) {
+        self.prompt = Some(prompt);
+        self.refresh_prompt();
+    }
+
     /// Clears the prompt from the screen.
     pub(crate) fn clear_prompt(&mut self) {
         // Refresh the prompt before we remove it, so that we know which line to refresh.
@@ -1196,9 +1976,19 @@ impl Screen {
             .as_ref()
             .and_then(|ref search| search.current_match());
         if let Some((line_index, _match_index)) = current_match {
-            self.scroll_to(line_index);
+            if self.search_restored {
+                // The restored position takes precedence over the match.
+                self.search_restored = false;
+            } else if self.search_incremental {
+                // This is an incremental search, which only jumps once the
+                // prompt is submitted.
+                self.scroll_to(line_index);
+            } else {
+                self.jump_to(line_index);
+            }
             self.refresh_matched_lines();
             self.refresh_overlay();
+            self.refresh_ruler();
             return Some(Action::Render);
         }
         None
@@ -1208,19 +1998,27 @@ impl Screen {
     pub(crate) fn search_finished(&mut self) -> Option<Action> {
         self.refresh_matched_lines();
         self.refresh_overlay();
+        self.refresh_ruler();
         Some(Action::Render)
     }
 
     /// Move the currently selected match to a new match.
-    pub(crate) fn move_match(&mut self, motion: MatchMotion) {
+    ///
+    /// Returns true if the current match changed.
+    pub(crate) fn move_match(&mut self, motion: MatchMotion) -> bool {
         self.refresh_matched_line();
-        if let Some(ref mut search) = self.search {
-            search.move_match(motion);
-            if let Some((line_index, _match_index)) = search.current_match() {
-                self.scroll_to(line_index);
-            }
-            self.refresh_matched_line();
+        let moved = match self.search {
+            Some(ref mut search) => (search.move_match(motion), search.current_match()),
+            None => return false,
+        };
+        match moved {
+            (true, Some((line_index, _match_index))) => self.jump_to(line_index),
+            (false, Some((line_index, _match_index))) => self.scroll_to(line_index),
+            (_, None) => {}
         }
+        self.refresh_matched_line();
+        self.refresh_ruler();
+        moved.0
     }
 
     pub(crate) fn flush_line_caches(&mut self) {
@@ -1231,7 +2029,13 @@ impl Screen {
     /// Load more lines from a stream.
     pub(crate) fn maybe_load_more(&mut self) {
         // Fetch 1 screen + config.read_ahead_lines.
-        let needed_lines = self.rendered.bottom_line + self.height + self.config.read_ahead_lines;
+        let needed_lines = self.file_line_index(self.rendered.bottom_line)
+            + self.height
+            + self.config.read_ahead_lines;
         self.file.set_needed_lines(needed_lines);
+        // Keep the previous run loaded far enough to compare with.
+        if let Some(ref previous_file) = self.previous_file {
+            previous_file.set_needed_lines(needed_lines);
+        }
     }
 }
@@ -2,25 +2,34 @@
 use anyhow::Error;
 use bit_set::BitSet;
 use lazy_static::lazy_static;
-use regex::bytes::{NoExpand, Regex};
+use regex::bytes::{NoExpand, Regex, RegexBuilder};
 use std::borrow::Cow;
-use std::cmp::min;
+use std::cell::Cell;
+use std::cmp::{max, min};
+use std::collections::HashMap;
+use std::ops::Range;
 use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
-use std::sync::{Arc, RwLock};
+use std::sync::{mpsc, Arc, Mutex, RwLock};
 use std::thread;
 use std::time;
 use termwiz::cell::CellAttributes;
 use termwiz::color::AnsiColor;
+use termwiz::input::{KeyCode, KeyEvent, Modifiers};
 use termwiz::surface::change::Change;
 use termwiz::surface::Position;
 use unicode_width::UnicodeWidthStr;
 
+use crate::config::{Config, SearchCase, SearchMode};
 use crate::event::{Event, EventSender};
 use crate::file::File;
 use crate::overstrike;
+use crate::prompt::PromptOptions;
 
 const SEARCH_BATCH_SIZE: usize = 10000;
 
+/// The maximum number of lines a multi-line match can span.
+const MULTILINE_MAX_LINES: usize = 100;
+
 lazy_static! {
     /// Regex for detecting and removing escape sequences during search.
     pub(crate) static ref ESCAPE_SEQUENCE: Regex = Regex::new("\x1B\\[[0123456789:;\\[?!\"'#%()*+ ]{0,32}m").unwrap();
@@ -32,6 +41,94 @@ pub(crate) enum SearchKind {
     First,
     FirstAfter(usize),
     FirstBefore(usize),
+    /// Find all matching lines without selecting a current match.
+    Filter,
+}
+
+/// Options that control how search patterns are matched.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub(crate) struct SearchOptions {
+    pub(crate) mode: SearchMode,
+    pub(crate) case: SearchCase,
+    /// Whether matches can span multiple lines.
+    pub(crate) multiline: bool,
+}
+
+impl SearchOptions {
+    /// Create the default search options for a configuration.
+    pub(crate) fn new(config: &Config) -> SearchOptions {
+        SearchOptions {
+            mode: config.search_mode,
+            case: config.search_case,
+            multiline: false,
+        }
+    }
+
+    /// Build the regex that matches a pattern using these options.
+    pub(crate) fn regex(self, pattern: &str) -> Result<Regex, Error> {
+        let case_insensitive = match self.case {
+            SearchCase::Sensitive => false,
+            SearchCase::Insensitive => true,
+            SearchCase::Smart => !has_uppercase(pattern, self.mode),
+        };
+        let pattern = match self.mode {
+            SearchMode::Regex => Cow::Borrowed(pattern),
+            SearchMode::Literal => Cow::Owned(regex::escape(pattern)),
+        };
+        Ok(RegexBuilder::new(&pattern)
+            .case_insensitive(case_insensitive)
+            .multi_line(self.multiline)
+            .build()?)
+    }
+}
+
+impl PromptOptions for Cell<SearchOptions> {
+    fn dispatch_key(&self, key: &KeyEvent) -> bool {
+        let mut options = self.get();
+        match (key.modifiers, key.key) {
+            (Modifiers::ALT, KeyCode::Char('r')) => options.mode = options.mode.next_mode(),
+            (Modifiers::ALT, KeyCode::Char('c')) => options.case = options.case.next_mode(),
+            (Modifiers::ALT, KeyCode::Char('m')) => options.multiline = !options.multiline,
+            _ => return false,
+        }
+        self.set(options);
+        true
+    }
+
+    fn describe(&self) -> String {
+        let options = self.get();
+        let mode = match options.mode {
+            SearchMode::Regex => "regex",
+            SearchMode::Literal => "literal",
+        };
+        let case = match options.case {
+            SearchCase::Sensitive => "match case",
+            SearchCase::Insensitive => "ignore case",
+            SearchCase::Smart => "smart case",
+        };
+        if options.multiline {
+            format!("{}, {}, multi-line", mode, case)
+        } else {
+            format!("{}, {}", mode, case)
+        }
+    }
+}
+
+/// Returns true if the pattern contains an uppercase character.  For regex
+/// patterns, characters that are part of an escape sequence (e.g. `\S`) are
+/// not counted.
+fn has_uppercase(pattern: &str, mode: SearchMode) -> bool {
+    let mut escaped = false;
+    for c in pattern.chars() {
+        if escaped {
+            escaped = false;
+        } else if c == '\\' && mode == SearchMode::Regex {
+            escaped = true;
+        } else if c.is_uppercase() {
+            return true;
+        }
+    }
+    false
 }
 
 /// Motion when changing search matches.
@@ -45,6 +142,10 @@ pub(crate) enum MatchMotion {
     Last,
 }
 
+/// The ranges of each line that are covered by multi-line matches, as
+/// `(start, end, match_index)`.
+type MultilineRanges = HashMap<usize, Vec<(usize, usize, usize)>>;
+
 /// Internal struct for searching in a file.  This is protected by an Arc so
 /// that it can be accessed from both the main screen thread and also the search
 /// thread.
@@ -52,12 +153,15 @@ struct SearchInner {
     pattern: String,
     kind: SearchKind,
     regex: Regex,
+    options: SearchOptions,
     matches: RwLock<Vec<(usize, usize)>>,
     matching_lines: RwLock<BitSet>,
+    multiline_ranges: RwLock<MultilineRanges>,
     current_match: RwLock<Option<usize>>,
     matching_line_count: AtomicUsize,
     search_line_count: AtomicUsize,
     finished: AtomicBool,
+    cancelled: AtomicBool,
 }
 
 /// A search for a pattern within a file.
@@ -65,84 +169,114 @@ pub(crate) struct Search {
     inner: Arc<SearchInner>,
 }
 
+/// A handle for observing the progress of a search, e.g. from the ruler.
+/// Unlike `Search`, dropping this does not stop the search.
+#[derive(Clone)]
+pub(crate) struct SearchProgress {
+    inner: Arc<SearchInner>,
+}
+
 impl SearchInner {
     /// Create a new SearchInner for a search.
     fn new(
         file: &File,
         pattern: &str,
+        options: SearchOptions,
         kind: SearchKind,
         event_sender: EventSender,
     ) -> Result<Arc<SearchInner>, Error> {
-        let regex = Regex::new(pattern)?;
+        let regex = options.regex(pattern)?;
         let search = Arc::new(SearchInner {
             pattern: pattern.to_string(),
             kind,
             regex: regex.clone(),
+            options,
             matches: RwLock::new(Vec::new()),
             matching_lines: RwLock::new(BitSet::new()),
+            multiline_ranges: RwLock::new(HashMap::new()),
             current_match: RwLock::new(None),
             matching_line_count: AtomicUsize::new(0),
             search_line_count: AtomicUsize::new(0),
             finished: AtomicBool::new(false),
+            cancelled: AtomicBool::new(false),
         });
         thread::spawn({
             let search = search.clone();
             let file = file.clone();
             move || {
                 let mut matched = false;
+                let mut select_first_match =
+                    |matches: &[(usize, usize)], line, first_match_index| {
+                        if matched {
+                            return;
+                        }
+                        if let Some(index) =
+                            first_match(search.kind, matches, line, first_match_index)
+                        {
+                            *search.current_match.write().unwrap() = Some(index);
+                            event_sender
+                                .send(Event::SearchFirstMatch(file.index()))
+                                .unwrap();
+                            matched = true;
+                        }
+                    };
+                let mut resume = (0, 0);
+                // Files on disk can be searched by several threads at once.
+                let workers = if file.is_on_disk() && !search.options.multiline {
+                    thread::available_parallelism()
+                        .map(|workers| workers.get())
+                        .unwrap_or(1)
+                } else {
+                    1
+                };
+                let workers = SearchWorkers::new(&file, &regex, workers);
                 loop {
+                    if search.cancelled.load(Ordering::SeqCst) {
+                        // The search is no longer needed.
+                        return;
+                    }
                     let loaded = file.loaded();
                     let lines = file.lines();
+                    let available = if loaded {
+                        lines
+                    } else {
+                        lines.saturating_sub(1)
+                    };
                     let search_line_count = search.search_line_count.load(Ordering::SeqCst);
-                    let search_limit = min(
-                        search_line_count + SEARCH_BATCH_SIZE,
-                        if loaded { lines } else { lines - 1 },
+                    // A multi-line match may continue onto lines that haven't
+                    // arrived yet, so leave room for it while still loading.
+                    let searchable = if search.options.multiline && !loaded {
+                        available.saturating_sub(MULTILINE_MAX_LINES)
+                    } else {
+                        available
+                    };
+                    let search_limit = max(
+                        search_line_count,
+                        min(
+                            search_line_count + SEARCH_BATCH_SIZE * workers.count,
+                            searchable,
+                        ),
                     );
-                    for line in search_line_count..search_limit {
-                        let count = file.with_line(line, |data| {
-                            // Strip trailing LF or CRLF if it is there.
-                            let len = trim_trailing_newline(&data[..]);
-                            let data = overstrike::convert_overstrike(&data[..len]);
-                            let data = ESCAPE_SEQUENCE.replace_all(&data[..], NoExpand(b""));
-                            regex.find_iter(&data[..]).count()
-                        });
-                        if count.unwrap_or(0) > 0 {
+                    if search.options.multiline {
+                        search.search_multiline(
+                            &file,
+                            search_line_count..search_limit,
+                            available,
+                            &mut resume,
+                            &mut select_first_match,
+                        );
+                    } else {
+                        let lines = search_line_count..search_limit;
+                        for (line, count) in workers.search(lines) {
                             let mut matching_lines = search.matching_lines.write().unwrap();
                             matching_lines.insert(line);
                             let mut matches = search.matches.write().unwrap();
                             let first_match_index = matches.len();
-                            for i in 0..count.unwrap() {
+                            for i in 0..count {
                                 matches.push((line, i));
                             }
                             search.matching_line_count.fetch_add(1, Ordering::SeqCst);
-                            if !matched {
-                                if let Some(index) = match search.kind {
-                                    SearchKind::First => Some(first_match_index),
-                                    SearchKind::FirstAfter(offset) => {
-                                        if line >= offset {
-                                            Some(first_match_index)
-                                        } else {
-                                            None
-                                        }
-                                    }
-                                    SearchKind::FirstBefore(offset) => {
-                                        if line >= offset
-                                            && first_match_index > 0
-                                            && matches[first_match_index - 1].0 < offset
-                                        {
-                                            Some(first_match_index - 1)
-                                        } else {
-                                            None
-                                        }
-                                    }
-                                } {
-                                    *search.current_match.write().unwrap() = Some(index);
-                                    event_sender
-                                        .send(Event::SearchFirstMatch(file.index()))
-                                        .unwrap();
-                                    matched = true;
-                                }
-                            }
+                            select_first_match(&matches, line, first_match_index);
                         }
                     }
                     search
@@ -152,16 +286,16 @@ impl SearchInner {
                         // Searched the whole file.
                         break;
                     }
-                    if !loaded && search_limit >= lines - 1 {
+                    if !loaded && search_limit >= searchable {
                         // Searched the whole file so far.  Wait for more data.
                         thread::sleep(time::Duration::from_millis(100));
                     }
                 }
-                if !matched {
+                if !matched && search.kind != SearchKind::Filter {
                     let matches = search.matches.read().unwrap();
                     if matches.len() > 0 {
                         let index = match search.kind {
-                            SearchKind::First | SearchKind::FirstAfter(_) => 0,
+                            SearchKind::First | SearchKind::FirstAfter(_) | SearchKind::Filter => 0,
                             SearchKind::FirstBefore(_) => matches.len() - 1,
                         };
                         *search.current_match.write().unwrap() = Some(index);
@@ -178,6 +312,234 @@ impl SearchInner {
         });
         Ok(search)
     }
+
+    /// Search for matches that start on the lines in `lines`, and which may
+    /// continue onto following lines, up to line `available`.
+    ///
+    /// `resume` is the line and offset of the end of the previous match, as
+    /// matches may not overlap.
+    fn search_multiline(
+        &self,
+        file: &File,
+        lines: Range<usize>,
+        available: usize,
+        resume: &mut (usize, usize),
+        select_first_match: &mut impl FnMut(&[(usize, usize)], usize, usize),
+    ) {
+        if lines.start == lines.end {
+            return;
+        }
+
+        // Join the lines together, recording where each line starts.
+        let end = min(lines.end + MULTILINE_MAX_LINES, available);
+        let mut text = Vec::new();
+        let mut line_starts = Vec::new();
+        for line in lines.start..end {
+            line_starts.push(text.len());
+            file.with_line(line, |data| text.extend_from_slice(&searchable_line(&data)));
+            text.push(b'\n');
+        }
+        let locate = |offset: usize| {
+            let index = match line_starts.binary_search(&offset) {
+                Ok(index) => index,
+                Err(index) => index - 1,
+            };
+            (lines.start + index, offset - line_starts[index])
+        };
+        let line_len = |line: usize| {
+            let index = line - lines.start;
+            let next_start = line_starts.get(index + 1).cloned().unwrap_or(text.len());
+            next_start - line_starts[index] - 1
+        };
+
+        for match_range in self.regex.find_iter(&text[..]) {
+            if match_range.start() >= text.len() {
+                break;
+            }
+            let start = locate(match_range.start());
+            if start.0 >= lines.end {
+                break;
+            }
+            if start < *resume {
+                continue;
+            }
+            let end = if match_range.end() > match_range.start() {
+                let (line, offset) = locate(match_range.end() - 1);
+                (line, offset + 1)
+            } else {
+                start
+            };
+            *resume = end;
+
+            let mut matches = self.matches.write().unwrap();
+            let mut matching_lines = self.matching_lines.write().unwrap();
+            let mut multiline_ranges = self.multiline_ranges.write().unwrap();
+            let match_index = matches.len();
+            let line_match_index = matches
+                .iter()
+                .rev()
+                .take_while(|&&(line, _)| line == start.0)
+                .count();
+            matches.push((start.0, line_match_index));
+            for line in start.0..=end.0 {
+                let range_start = if line == start.0 { start.1 } else { 0 };
+                let range_end = if line == end.0 {
+                    min(end.1, line_len(line))
+                } else {
+                    line_len(line)
+                };
+                multiline_ranges.entry(line).or_default().push((
+                    range_start,
+                    range_end,
+                    match_index,
+                ));
+                if matching_lines.insert(line) {
+                    self.matching_line_count.fetch_add(1, Ordering::SeqCst);
+                }
+            }
+            select_first_match(&matches, start.0, match_index);
+        }
+    }
+}
+
+/// A search job: the lines to search, and where to send the results.
+type SearchJob = (Range<usize>, mpsc::Sender<Vec<(usize, usize)>>);
+
+/// The threads that search a file, which last for the whole search.
+///
+/// If there is more than one worker, each batch of lines is split into
+/// chunks that are searched on separate threads.
+struct SearchWorkers {
+    file: File,
+    regex: Regex,
+    count: usize,
+    jobs: Option<mpsc::Sender<SearchJob>>,
+}
+
+impl SearchWorkers {
+    fn new(file: &File, regex: &Regex, count: usize) -> SearchWorkers {
+        let jobs = if count > 1 {
+            let (sender, receiver) = mpsc::channel::<SearchJob>();
+            let receiver = Arc::new(Mutex::new(receiver));
+            for _ in 0..count {
+                let file = file.clone();
+                let regex = regex.clone();
+                let receiver = receiver.clone();
+                thread::spawn(move || loop {
+                    // The workers stop once the sender is dropped.
+                    let job = receiver.lock().unwrap().recv();
+                    match job {
+                        Ok((lines, results)) => {
+                            let _ = results.send(search_chunk(&file, &regex, lines));
+                        }
+                        Err(_) => return,
+                    }
+                });
+            }
+            Some(sender)
+        } else {
+            None
+        };
+        SearchWorkers {
+            file: file.clone(),
+            regex: regex.clone(),
+            count,
+            jobs,
+        }
+    }
+
+    /// Search the lines in `lines`, returning each line that matches along
+    /// with the number of matches on that line, in order.
+    fn search(&self, lines: Range<usize>) -> Vec<(usize, usize)> {
+        let chunk_size = (lines.end - lines.start).div_ceil(self.count);
+        let jobs = match self.jobs {
+            Some(ref jobs) if chunk_size > 0 => jobs,
+            _ => return search_chunk(&self.file, &self.regex, lines),
+        };
+        let results: Vec<_> = lines
+            .clone()
+            .step_by(chunk_size)
+            .map(|start| {
+                let (sender, receiver) = mpsc::channel();
+                let end = min(start + chunk_size, lines.end);
+                jobs.send((start..end, sender))
+                    .expect("search workers stopped");
+                receiver
+            })
+            .collect();
+        results
+            .into_iter()
+            .flat_map(|results| results.recv().expect("search worker panicked"))
+            .collect()
+    }
+}
+
+/// Search the lines in `lines` on the current thread, returning each line
+/// that matches along with the number of matches on that line.
+fn search_chunk(file: &File, regex: &Regex, lines: Range<usize>) -> Vec<(usize, usize)> {
+    lines
+        .filter_map(|line| {
+            let count = file.with_line_unlocked(line, |data| {
+                regex.find_iter(&searchable_line(&data)[..]).count()
+            })?;
+            if count > 0 {
+                Some((line, count))
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+/// Returns the index of the match that should be selected first for a
+/// search of this kind, if it is one of the matches on `line`, which start
+/// at `first_match_index`.
+fn first_match(
+    kind: SearchKind,
+    matches: &[(usize, usize)],
+    line: usize,
+    first_match_index: usize,
+) -> Option<usize> {
+    match kind {
+        SearchKind::First => Some(first_match_index),
+        SearchKind::FirstAfter(offset) => {
+            if line >= offset {
+                Some(first_match_index)
+            } else {
+                None
+            }
+        }
+        SearchKind::FirstBefore(offset) => {
+            if line >= offset && first_match_index > 0 && matches[first_match_index - 1].0 < offset
+            {
+                Some(first_match_index - 1)
+            } else {
+                None
+            }
+        }
+        SearchKind::Filter => None,
+    }
+}
+
+/// Returns the content of a line as it is searched: without the trailing
+/// newline, with overstrike converted, and with escape sequences removed.
+fn searchable_line(data: &[u8]) -> Cow<'_, [u8]> {
+    let len = trim_trailing_newline(data);
+    match overstrike::convert_overstrike(&data[..len]) {
+        Cow::Borrowed(data) => ESCAPE_SEQUENCE.replace_all(data, NoExpand(b"")),
+        Cow::Owned(data) => Cow::Owned(
+            ESCAPE_SEQUENCE
+                .replace_all(&data[..], NoExpand(b""))
+                .into_owned(),
+        ),
+    }
+}
+
+impl Drop for Search {
+    fn drop(&mut self) {
+        // Stop the search thread, as nothing will see its results.
+        self.inner.cancelled.store(true, Ordering::SeqCst);
+    }
 }
 
 impl Search {
@@ -185,11 +547,12 @@ impl Search {
     pub(crate) fn new(
         file: &File,
         pattern: &str,
+        options: SearchOptions,
         kind: SearchKind,
         event_sender: EventSender,
     ) -> Result<Search, Error> {
         Ok(Search {
-            inner: SearchInner::new(file, pattern, kind, event_sender)?,
+            inner: SearchInner::new(file, pattern, options, kind, event_sender)?,
         })
     }
 
@@ -198,6 +561,13 @@ impl Search {
         self.inner.finished.load(Ordering::SeqCst)
     }
 
+    /// Returns a handle for observing the progress of this search.
+    pub(crate) fn progress(&self) -> SearchProgress {
+        SearchProgress {
+            inner: self.inner.clone(),
+        }
+    }
+
     /// Renders the search overlay line.
     pub(crate) fn render(
         &mut self,
@@ -274,11 +644,14 @@ impl Search {
     }
 
     /// Moves to another match if there is one.
-    pub(crate) fn move_match(&mut self, motion: MatchMotion) {
+    ///
+    /// Returns true if the current match changed.
+    pub(crate) fn move_match(&mut self, motion: MatchMotion) -> bool {
         let matches = self.inner.matches.read().unwrap();
         if matches.len() > 0 {
             let mut current_match_index = self.inner.current_match.write().unwrap();
             if let Some(ref mut index) = *current_match_index {
+                let old_index = *index;
                 match motion {
                     MatchMotion::First => *index = 0,
                     MatchMotion::PreviousLine => {
@@ -302,8 +675,10 @@ impl Search {
                     MatchMotion::Last => *index = matches.len() - 1,
                     _ => {}
                 }
+                return *index != old_index;
             }
         }
+        false
     }
 
     /// Returns the lines in the given range that match.
@@ -323,6 +698,16 @@ impl Search {
         self.inner.search_line_count.load(Ordering::SeqCst)
     }
 
+    /// Returns the pattern used for this search.
+    pub(crate) fn pattern(&self) -> &str {
+        &self.inner.pattern
+    }
+
+    /// Returns the options used for this search.
+    pub(crate) fn options(&self) -> SearchOptions {
+        self.inner.options
+    }
+
     /// Returns the Regex used for this search.
     pub(crate) fn regex(&self) -> &Regex {
         &self.inner.regex
@@ -330,12 +715,94 @@ impl Search {
 
     /// Returns true if the line index matches the search
     pub(crate) fn line_matches(&self, line_index: usize) -> bool {
+        // Lines that haven't been searched yet may still gain more matches
+        // from a multi-line search, so wait until they have been searched.
+        if self.inner.options.multiline && line_index >= self.searched_lines() {
+            return false;
+        }
         self.inner
             .matching_lines
             .read()
             .unwrap()
             .contains(line_index)
     }
+
+    /// Returns the ranges of the line that are covered by multi-line
+    /// matches, and the index of the match that covers each range.  Returns
+    /// `None` if this is not a multi-line search.
+    pub(crate) fn multiline_ranges(&self, line_index: usize) -> Option<Vec<(usize, usize, usize)>> {
+        if !self.inner.options.multiline {
+            return None;
+        }
+        let multiline_ranges = self.inner.multiline_ranges.read().unwrap();
+        Some(
+            multiline_ranges
+                .get(&line_index)
+                .cloned()
+                .unwrap_or_default(),
+        )
+    }
+
+    /// Returns the first and last lines covered by the current match.
+    pub(crate) fn current_match_lines(&self) -> Option<(usize, usize)> {
+        let (start_line, _) = self.current_match()?;
+        if !self.inner.options.multiline {
+            return Some((start_line, start_line));
+        }
+        let current_match_index = (*self.inner.current_match.read().unwrap())?;
+        let multiline_ranges = self.inner.multiline_ranges.read().unwrap();
+        let mut end_line = start_line;
+        while multiline_ranges
+            .get(&(end_line + 1))
+            .map(|ranges| ranges.iter().any(|range| range.2 == current_match_index))
+            .unwrap_or(false)
+        {
+            end_line += 1;
+        }
+        Some((start_line, end_line))
+    }
+
+    /// Returns the index that the current match is marked with in the line,
+    /// if the current match is in the line.  Multi-line matches are marked
+    /// with their overall match index on every line they cover, otherwise
+    /// matches are marked with their index within the line.
+    pub(crate) fn current_match_in_line(&self, line_index: usize) -> Option<usize> {
+        if self.inner.options.multiline {
+            let current_match_index = (*self.inner.current_match.read().unwrap())?;
+            let multiline_ranges = self.inner.multiline_ranges.read().unwrap();
+            multiline_ranges
+                .get(&line_index)?
+                .iter()
+                .find(|&&(_, _, match_index)| match_index == current_match_index)
+                .map(|_| current_match_index)
+        } else {
+            self.current_match()
+                .filter(|&(match_line_index, _)| match_line_index == line_index)
+                .map(|(_, match_index)| match_index)
+        }
+    }
+}
+
+impl SearchProgress {
+    /// Returns the index of the current match, if there is one.
+    pub(crate) fn current_match_index(&self) -> Option<usize> {
+        *self.inner.current_match.read().unwrap()
+    }
+
+    /// Returns the number of matches found so far.
+    pub(crate) fn match_count(&self) -> usize {
+        self.inner.matches.read().unwrap().len()
+    }
+
+    /// Returns the number of lines with matches found so far.
+    pub(crate) fn matching_line_count(&self) -> usize {
+        self.inner.matching_line_count.load(Ordering::SeqCst)
+    }
+
+    /// Returns true if the search has finished searching the whole file.
+    pub(crate) fn finished(&self) -> bool {
+        self.inner.finished.load(Ordering::SeqCst)
+    }
 }
 
 pub(crate) fn trim_trailing_newline(data: impl AsRef<[u8]>) -> usize {
@@ -0,0 +1,370 @@
+//! Sessions.
+//!
+//! When the pager exits, the position, presentation and search of each file
+//! that was read from disk are stored, so that they can be restored the next
+//! time the same file is opened.
+use anyhow::Error;
+use flate2::Crc;
+use std::cmp::min;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+use tempfile::NamedTempFile;
+
+use crate::config::{SearchCase, SearchMode, WrappingMode};
+use crate::search::SearchOptions;
+
+/// The number of files whose sessions are kept.
+const SESSION_COUNT: usize = 1000;
+
+/// The number of bytes at the start of a file that are checked to see if
+/// the file has been replaced.
+const PREFIX_LENGTH: u64 = 4096;
+
+/// Identifies a file on disk, and the state of its content.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct FileKey {
+    /// The canonical path of the file.
+    path: String,
+
+    /// The size of the file.
+    size: u64,
+
+    /// The modification time of the file, in nanoseconds since the epoch.
+    mtime: u128,
+
+    /// The checksum of the first `PREFIX_LENGTH` bytes of the file, or of the
+    /// whole file if it is shorter.
+    prefix_checksum: u32,
+}
+
+impl FileKey {
+    /// Identify the file at `path`.
+    fn new(path: &Path) -> Option<FileKey> {
+        let path = path.canonicalize().ok()?;
+        let metadata = path.metadata().ok()?;
+        let mtime = metadata
+            .modified()
+            .ok()?
+            .duration_since(UNIX_EPOCH)
+            .ok()?
+            .as_nanos();
+        let size = metadata.len();
+        Some(FileKey {
+            prefix_checksum: prefix_checksum(&path, min(size, PREFIX_LENGTH))?,
+            path: path.to_string_lossy().into_owned(),
+            size,
+            mtime,
+        })
+    }
+
+    /// Returns true if a session saved for `saved` can be used for this
+    /// file.  The file must be unchanged, or have only grown since, as
+    /// happens when a log file is appended to.  A file that has grown must
+    /// still start with the same data.
+    fn resumes(&self, saved: &FileKey) -> bool {
+        if self.path != saved.path {
+            return false;
+        }
+        if self.size == saved.size {
+            return self.mtime == saved.mtime && self.prefix_checksum == saved.prefix_checksum;
+        }
+        if self.size < saved.size {
+            return false;
+        }
+        if saved.size >= PREFIX_LENGTH {
+            self.prefix_checksum == saved.prefix_checksum
+        } else {
+            prefix_checksum(Path::new(&self.path), saved.size) == Some(saved.prefix_checksum)
+        }
+    }
+}
+
+/// Returns the checksum of the first `length` bytes of the file at `path`.
+fn prefix_checksum(path: &Path, length: u64) -> Option<u32> {
+    let mut data = Vec::new();
+    File::open(path)
+        .ok()?
+        .take(length)
+        .read_to_end(&mut data)
+        .ok()?;
+    if data.len() as u64 != length {
+        return None;
+    }
+    let mut crc = Crc::new();
+    crc.update(&data);
+    Some(crc.sum())
+}
+
+/// The state of a screen that is kept between runs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Session {
+    /// The file line at the top of the screen.
+    pub(crate) top_line: usize,
+
+    /// The wrapping mode.
+    pub(crate) wrapping_mode: WrappingMode,
+
+    /// Whether line numbers are shown.
+    pub(crate) line_numbers: bool,
+
+    /// The pattern and options of the last search.
+    pub(crate) search: Option<(String, SearchOptions)>,
+}
+
+impl Session {
+    /// Load the session that was saved for the file at `path`, if there is
+    /// one and the file has not been replaced since.
+    pub(crate) fn load(path: &Path) -> Option<Session> {
+        let key = FileKey::new(path)?;
+        let file = BufReader::new(File::open(sessions_path()?).ok()?);
+        let mut session = None;
+        for line in file.lines() {
+            if let Some((saved_key, saved_session)) = line.ok().as_deref().and_then(parse_entry) {
+                if saved_key.path == key.path {
+                    session = if key.resumes(&saved_key) {
+                        Some(saved_session)
+                    } else {
+                        None
+                    };
+                }
+            }
+        }
+        session
+    }
+
+    /// Save the session for the file at `path`, replacing any session that
+    /// was previously saved for it.
+    pub(crate) fn save(&self, path: &Path) -> Result<(), Error> {
+        let (key, sessions_path) = match (FileKey::new(path), sessions_path()) {
+            (Some(key), Some(sessions_path)) => (key, sessions_path),
+            _ => return Ok(()),
+        };
+        let mut entries = Vec::new();
+        if let Ok(file) = File::open(&sessions_path) {
+            for line in BufReader::new(file).lines() {
+                let line = line?;
+                match parse_entry(&line) {
+                    Some((saved_key, _)) if saved_key.path == key.path => {}
+                    Some(_) => entries.push(line),
+                    None => {}
+                }
+            }
+        }
+        entries.push(format_entry(&key, self));
+        let dir = sessions_path.parent().expect("sessions path has a parent");
+        std::fs::create_dir_all(dir)?;
+        let mut new_file = NamedTempFile::new_in(dir)?;
+        for entry in entries
+            .iter()
+            .skip(entries.len().saturating_sub(SESSION_COUNT))
+        {
+            writeln!(new_file, "{}", entry)?;
+        }
+        new_file.persist(&sessions_path)?;
+        Ok(())
+    }
+}
+
+/// Returns the path of the file that sessions are stored in.
+fn sessions_path() -> Option<PathBuf> {
+    let mut path = dirs::data_dir()?;
+    path.push("streampager");
+    path.push("sessions");
+    Some(path)
+}
+
+/// Format a session as a line in the sessions file.  Fields are separated
+/// by tabs.
+fn format_entry(key: &FileKey, session: &Session) -> String {
+    let wrapping_mode = match session.wrapping_mode {
+        WrappingMode::Unwrapped => "none",
+        WrappingMode::GraphemeBoundary => "line",
+        WrappingMode::WordBoundary => "word",
+    };
+    let mut entry = format!(
+        "{}\t{}\t{}\t{}\t{}\t{}\t{}",
+        escape(&key.path),
+        key.size,
+        key.mtime,
+        key.prefix_checksum,
+        session.top_line,
+        wrapping_mode,
+        session.line_numbers,
+    );
+    if let Some((pattern, options)) = &session.search {
+        let mode = match options.mode {
+            SearchMode::Regex => "regex",
+            SearchMode::Literal => "literal",
+        };
+        let case = match options.case {
+            SearchCase::Sensitive => "sensitive",
+            SearchCase::Insensitive => "insensitive",
+            SearchCase::Smart => "smart",
+        };
+        entry.push_str(&format!(
+            "\t{}\t{}\t{}\t{}",
+            mode,
+            case,
+            options.multiline,
+            escape(pattern)
+        ));
+    }
+    entry
+}
+
+/// Parse a line in the sessions file.
+fn parse_entry(entry: &str) -> Option<(FileKey, Session)> {
+    let mut fields = entry.split('\t');
+    let key = FileKey {
+        path: unescape(fields.next()?),
+        size: fields.next()?.parse().ok()?,
+        mtime: fields.next()?.parse().ok()?,
+        prefix_checksum: fields.next()?.parse().ok()?,
+    };
+    let top_line = fields.next()?.parse().ok()?;
+    let wrapping_mode = match fields.next()? {
+        "none" => WrappingMode::Unwrapped,
+        "line" => WrappingMode::GraphemeBoundary,
+        "word" => WrappingMode::WordBoundary,
+        _ => return None,
+    };
+    let line_numbers = fields.next()?.parse().ok()?;
+    let search = match fields.next() {
+        Some(mode) => {
+            let mode = match mode {
+                "regex" => SearchMode::Regex,
+                "literal" => SearchMode::Literal,
+                _ => return None,
+            };
+            let case = match fields.next()? {
+                "sensitive" => SearchCase::Sensitive,
+                "insensitive" => SearchCase::Insensitive,
+                "smart" => SearchCase::Smart,
+                _ => return None,
+            };
+            let multiline = fields.next()?.parse().ok()?;
+            let pattern = unescape(fields.next()?);
+            let options = SearchOptions {
+                mode,
+                case,
+                multiline,
+            };
+            Some((pattern, options))
+        }
+        None => None,
+    };
+    let session = Session {
+        top_line,
+        wrapping_mode,
+        line_numbers,
+        search,
+    };
+    Some((key, session))
+}
+
+/// Escape a value so that it does not contain tabs or newlines.
+fn escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '\t' => escaped.push_str("\\t"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Reverse `escape`.
+fn unescape(value: &str) -> String {
+    let mut unescaped = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            match chars.next() {
+                Some('t') => unescaped.push('\t'),
+                Some('n') => unescaped.push('\n'),
+                Some('r') => unescaped.push('\r'),
+                Some(c) => unescaped.push(c),
+                None => {}
+            }
+        } else {
+            unescaped.push(c);
+        }
+    }
+    unescaped
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_entry_round_trip() {
+        let key = FileKey {
+            path: String::from("/var/log/tab\there"),
+            size: 1234,
+            mtime: 1_600_000_000_123_456_789,
+            prefix_checksum: 0xdead_beef,
+        };
+        let session = Session {
+            top_line: 42,
+            wrapping_mode: WrappingMode::WordBoundary,
+            line_numbers: true,
+            search: Some((
+                String::from("error\\s+\t[0-9]"),
+                SearchOptions {
+                    mode: SearchMode::Regex,
+                    case: SearchCase::Insensitive,
+                    multiline: false,
+                },
+            )),
+        };
+        let entry = format_entry(&key, &session);
+        assert_eq!(entry.matches('\t').count(), 10);
+        assert_eq!(parse_entry(&entry), Some((key.clone(), session.clone())));
+
+        let session = Session {
+            search: None,
+            ..session
+        };
+        assert_eq!(
+            parse_entry(&format_entry(&key, &session)),
+            Some((key, session))
+        );
+    }
+
+    #[test]
+    fn test_resumes() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("log");
+        std::fs::write(&path, "first\n").unwrap();
+        let saved = FileKey::new(&path).unwrap();
+        assert!(FileKey::new(&path).unwrap().resumes(&saved));
+
+        // Appending to the file keeps the session.
+        std::fs::write(&path, "first\nsecond\n").unwrap();
+        assert!(FileKey::new(&path).unwrap().resumes(&saved));
+
+        // Replacing the file with a larger one doesn't.
+        std::fs::write(&path, "other\nsecond\n").unwrap();
+        assert!(!FileKey::new(&path).unwrap().resumes(&saved));
+
+        // Nor does truncating it.
+        std::fs::write(&path, "fir").unwrap();
+        assert!(!FileKey::new(&path).unwrap().resumes(&saved));
+
+        // Once the start of the file is long enough, only that is checked.
+        let start = "x".repeat(PREFIX_LENGTH as usize);
+        std::fs::write(&path, &start).unwrap();
+        let saved = FileKey::new(&path).unwrap();
+        std::fs::write(&path, format!("{}more", start)).unwrap();
+        assert!(FileKey::new(&path).unwrap().resumes(&saved));
+        std::fs::write(&path, format!("y{}", start)).unwrap();
+        assert!(!FileKey::new(&path).unwrap().resumes(&saved));
+    }
+}
@@ -1,8 +1,41 @@
 //! Utilities.
 use std::borrow::Cow;
+use std::env;
+use std::ffi::OsString;
+use std::process::Command;
 use unicode_segmentation::UnicodeSegmentation;
 use unicode_width::UnicodeWidthStr;
 
+/// Returns the user's shell, for running shell commands.
+pub(crate) fn shell() -> OsString {
+    env::var_os("SHELL")
+        .filter(|shell| !shell.is_empty())
+        .unwrap_or_else(|| OsString::from("sh"))
+}
+
+/// Connect the standard input, output and error of `command` to the terminal.
+///
+/// The pager's own standard input may be the data being paged, so programs
+/// that the user interacts with must read from the terminal instead, as the
+/// pager does.
+#[cfg(unix)]
+pub(crate) fn attach_to_terminal(command: &mut Command) -> std::io::Result<&mut Command> {
+    use std::fs::OpenOptions;
+    use std::process::Stdio;
+
+    let tty = OpenOptions::new().read(true).write(true).open("/dev/tty")?;
+    Ok(command
+        .stdin(Stdio::from(tty.try_clone()?))
+        .stdout(Stdio::from(tty.try_clone()?))
+        .stderr(Stdio::from(tty)))
+}
+
+/// Commands use the pager's standard streams on other platforms.
+#[cfg(not(unix))]
+pub(crate) fn attach_to_terminal(command: &mut Command) -> std::io::Result<&mut Command> {
+    Ok(command)
+}
+
 /// Returns the maximum width in characters of a number.
 pub(crate) fn number_width(number: usize) -> usize {
     let mut width = 1;